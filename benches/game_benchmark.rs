@@ -1,6 +1,6 @@
 use ai_2048::{
     agent::Agent,
-    agent::{expectimax::Expectimax, random::RandomAgent},
+    agent::random::RandomAgent,
     game::{Game, Move},
 };
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
@@ -10,8 +10,7 @@ fn criterion_benchmark(c: &mut Criterion) {
         b.iter_batched(
             || {
                 let game = black_box(Game::new_seeded(0));
-                let agent = black_box(RandomAgent::new_seeded(0, game));
-                agent
+                black_box(RandomAgent::new_seeded(1, game))
             },
             |mut agent| {
                 while !agent.get_game().game_over() {
@@ -0,0 +1,220 @@
+//! The classic human strategy: keep the biggest tile in a corner and pile the rest up against it.
+//!
+//! The agent never looks ahead. It tries moves in a fixed order, first the two that push towards
+//! its corner, then the one along the corner's wall, and only moves away from the wall when
+//! nothing else is possible.
+
+use strum_macros::{Display, EnumIter};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::game::{board::Board, outcome::MoveOutcome, Game, Move};
+
+use super::{Agent, TuiAgent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display, EnumIter)]
+pub enum Corner {
+    #[strum(serialize = "top left")]
+    TopLeft,
+    #[strum(serialize = "top right")]
+    TopRight,
+    #[default]
+    #[strum(serialize = "bottom left")]
+    BottomLeft,
+    #[strum(serialize = "bottom right")]
+    BottomRight,
+}
+
+impl Corner {
+    /// Moves from most to least preferred: towards the corner's row, towards its column, along
+    /// its row, and last the forbidden move away from its row.
+    pub fn move_order(self) -> [Move; 4] {
+        let (vertical, away) = match self {
+            Corner::TopLeft | Corner::TopRight => (Move::Up, Move::Down),
+            Corner::BottomLeft | Corner::BottomRight => (Move::Down, Move::Up),
+        };
+        let (horizontal, along) = match self {
+            Corner::TopLeft | Corner::BottomLeft => (Move::Left, Move::Right),
+            Corner::TopRight | Corner::BottomRight => (Move::Right, Move::Left),
+        };
+        [vertical, horizontal, along, away]
+    }
+
+    /// The move that pulls tiles out of the corner's row.
+    pub fn forbidden(self) -> Move {
+        self.move_order()[3]
+    }
+
+    /// The `(x, y)` cell of the corner on a board.
+    pub fn cell(self, board: &Board) -> (usize, usize) {
+        let right = board.width() - 1;
+        let bottom = board.height() - 1;
+        match self {
+            Corner::TopLeft => (0, 0),
+            Corner::TopRight => (right, 0),
+            Corner::BottomLeft => (0, bottom),
+            Corner::BottomRight => (right, bottom),
+        }
+    }
+
+    /// Whether the biggest tile on the board sits in the corner.
+    pub fn holds_max(self, board: &Board) -> bool {
+        let (x, y) = self.cell(board);
+        board.get(x, y) == board.max_exponent()
+    }
+}
+
+/// Why the agent picked its move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The first move in order that leaves the biggest tile in the corner.
+    KeepsCorner,
+    /// No allowed move leaves the biggest tile in the corner, so the first allowed move.
+    FirstAllowed,
+    /// Only the forbidden move was possible.
+    Forced,
+}
+
+/// Picks a move for a corner, with the reason it was picked.
+///
+/// # Panics
+/// If the game is over.
+pub fn corner_move(game: &Game, corner: Corner) -> (Move, Reason) {
+    let order = corner.move_order();
+    let allowed = order[..3]
+        .iter()
+        .copied()
+        .filter(|m| game.can_move(*m))
+        .collect::<Vec<_>>();
+    if let Some(m) = allowed
+        .iter()
+        .find(|m| corner.holds_max(&game.get_board().shift(**m).0))
+    {
+        return (*m, Reason::KeepsCorner);
+    }
+    if let Some(m) = allowed.first() {
+        return (*m, Reason::FirstAllowed);
+    }
+    assert!(
+        game.can_move(corner.forbidden()),
+        "no move is possible in a game that is over"
+    );
+    (corner.forbidden(), Reason::Forced)
+}
+
+pub struct CornerAgent {
+    game: Game,
+    corner: Corner,
+    last_move: Option<(Move, Reason)>,
+    last_outcome: Option<MoveOutcome>,
+}
+
+impl CornerAgent {
+    pub fn new(game: Game) -> Self {
+        CornerAgent::new_with(game, Corner::default())
+    }
+
+    pub fn new_with(game: Game, corner: Corner) -> Self {
+        CornerAgent {
+            game,
+            corner,
+            last_move: None,
+            last_outcome: None,
+        }
+    }
+}
+
+impl Agent for CornerAgent {
+    fn next_move(&self) -> Move {
+        corner_move(&self.game, self.corner).0
+    }
+
+    fn make_move(&mut self) {
+        let (m, reason) = corner_move(&self.game, self.corner);
+        self.last_move = Some((m, reason));
+        self.last_outcome = Some(self.game.make_move(m));
+    }
+
+    fn get_game(&self) -> &Game {
+        &self.game
+    }
+}
+
+impl TuiAgent for CornerAgent {
+    fn messages(&self) -> Vec<Spans<'_>> {
+        let [first, second, third, forbidden] = self.corner.move_order();
+        let mut msgs = vec![
+            Spans::from(Span::styled(
+                "Corner Strategy",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Spans::from(format!(
+                "Keeping the biggest tile in the {} corner, trying {}, {} then {}, and {} only when forced.",
+                self.corner, first, second, third, forbidden
+            )),
+            Spans::from(""),
+        ];
+        if let Some((m, reason)) = self.last_move {
+            msgs.push(Spans::from(match reason {
+                Reason::KeepsCorner => format!("Played {}: it keeps the biggest tile in the corner.", m),
+                Reason::FirstAllowed => format!(
+                    "Played {}: no move keeps the biggest tile in the corner, so the first one possible.",
+                    m
+                ),
+                Reason::Forced => format!("Played {}: it was the only move possible.", m),
+            }));
+        }
+        msgs
+    }
+
+    fn last_outcome(&self) -> Option<&MoveOutcome> {
+        self.last_outcome.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn test_corner_move() {
+        // Down and Left are blocked, Right would move the 2048 out of the corner
+        let game = "4 . . ./2 . . ./8 . . ./2048 2 . ."
+            .parse::<Game>()
+            .unwrap();
+        assert_eq!(
+            corner_move(&game, Corner::BottomLeft),
+            (Move::Right, Reason::FirstAllowed)
+        );
+
+        let game = ". . . ./2 4 2 4/4 2 4 2/2 4 2 4".parse::<Game>().unwrap();
+        assert_eq!(
+            corner_move(&game, Corner::BottomLeft),
+            (Move::Up, Reason::Forced)
+        );
+
+        for corner in Corner::iter() {
+            let mut agent = CornerAgent::new_with(Game::new_seeded(3), corner);
+            while !agent.get_game().game_over() {
+                let game = *agent.get_game();
+                agent.make_move();
+                let (m, reason) = agent.last_move.unwrap();
+                if m == corner.forbidden() {
+                    assert_eq!(reason, Reason::Forced);
+                    assert_eq!(game.available_moves().count(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "game that is over")]
+    fn test_game_over() {
+        let game = "2 4/4 2".parse::<Game>().unwrap();
+        corner_move(&game, Corner::default());
+    }
+}
@@ -1,6 +1,14 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use fastrand::shuffle;
+use enum_map::EnumMap;
 use rayon::prelude::*;
 use strum::IntoEnumIterator;
 use tui::{
@@ -8,160 +16,227 @@ use tui::{
     text::{Span, Spans},
 };
 
-use crate::game::{Game, Move};
+use crate::game::{
+    board::MAX_CELLS, outcome::MoveOutcome, rng::Rng, rules::MAX_SPAWN_KINDS, Game, Move,
+};
 
-use super::{random::simulate_random_game, Agent, MaxMove, MoveScores, TuiAgent};
+use super::{
+    heuristic::{GameOverHeuristic, Heuristic},
+    policy::{Decision, Policy},
+    transposition::TranspositionTable,
+    Agent, MaxMove, MoveScores, TuiAgent,
+};
 
+/// Plays its own game with an [`ExpectimaxPolicy`], reporting what each search did.
 pub struct Expectimax {
     game: Game,
-    params: ExpectimaxParams,
+    policy: ExpectimaxPolicy,
     last_scores: MoveScores,
+    last_stats: SearchStats,
+    last_outcome: Option<MoveOutcome>,
+    // the last position searched, with what the search found, so that asking for the next move
+    // and then playing it searches once
+    searched: Mutex<Option<(Game, MoveValues, SearchStats)>>,
 }
 
-pub struct ExpectimaxParams {
-    seed: Option<u64>,             // rand seed
-    max_evals: usize,              // how many nodes will we evaluate before using a heuristic?
-    curr_evals: Arc<Mutex<usize>>, // a mutexed counter for how many moves we've evaluated
-    num_tiles: usize, // what is the maximum number of tiles will we evaluate at the expectation step?
-    heuristic_sims: usize, // how many random simulations will we do as a "heuristic" for board evaluation
+/// Expectimax search for any position: the search settings, and the transposition table the
+/// searches share.
+pub struct ExpectimaxPolicy {
+    params: ExpectimaxParams,
+    // kept between moves, since the positions searched next turn were mostly searched this turn
+    table: Option<TranspositionTable>,
 }
 
-impl ExpectimaxParams {
-    fn add_count(&self) {
-        let mut e = self.curr_evals.lock().unwrap();
-        *e += 1;
-    }
-
-    fn curr_count(&self) -> usize {
-        let e = self.curr_evals.lock().unwrap();
-        e.to_owned()
-    }
-
-    fn reset_count(&self) {
-        let mut e = self.curr_evals.lock().unwrap();
-        *e = 0;
-    }
+/// Settings of the search. Every line of play is searched to the same depth, except those whose
+/// spawns are so unlikely that they are cut short by `min_probability`.
+///
+/// With a `time_budget`, the search deepens one move at a time, from 1 up to `depth`, until the
+/// budget runs out, and plays the best move of the deepest search that completed.
+#[derive(Debug, Clone)]
+pub struct ExpectimaxParams {
+    /// Rand seed, mixed with each board for its simulations.
+    pub seed: u64,
+    /// How many moves to look ahead, counting the move being chosen. Positions further away are
+    /// scored by the heuristic. With a `time_budget`, this is the deepest the search may go.
+    pub depth: usize,
+    /// Lines of play whose spawns are, all together, less likely than this are scored by the
+    /// heuristic instead of searched.
+    pub min_probability: f32,
+    /// Scores the positions at the end of each line of play.
+    pub heuristic: Arc<dyn Heuristic>,
+    /// The most positions the transposition table holds, or 0 to search without one. The table is
+    /// never larger than searches to `depth` can fill, so shallow searches don't pay for a large
+    /// table; one to depth 1 stores nothing, and goes without. Positions are cached by board size,
+    /// board, score, rules, depth and how likely the spawns leading to them were, treating boards
+    /// that are rotations or reflections of each other as one. The table is kept across moves, and
+    /// across games when an [`ExpectimaxPolicy`] is reused; since values count the score so far, a
+    /// board reached with a different score is searched afresh rather than read from the table.
+    /// With the table, searches running on several threads may differ slightly from run to run,
+    /// depending on which thread stores a position first.
+    pub table_size: usize,
+    /// Wall-clock time to spend on each move, or `None` to always search to `depth`. A search to
+    /// depth 1 always completes, so a move may take longer than this on very slow machines.
+    pub time_budget: Option<Duration>,
 }
 
 impl Default for ExpectimaxParams {
     fn default() -> Self {
         ExpectimaxParams {
-            seed: None,
-            max_evals: 500,
-            curr_evals: Arc::new(Mutex::new(0)),
-            num_tiles: 16,
-            heuristic_sims: 10,
+            seed: fastrand::u64(..),
+            depth: 2,
+            min_probability: 1e-3,
+            heuristic: Arc::new(GameOverHeuristic { sims: 10 }),
+            table_size: 1 << 18,
+            time_budget: None,
         }
     }
 }
 
-trait GameHeuristic {
-    fn score(game: &Game, params: &ExpectimaxParams) -> f32;
+/// A generator that depends only on the agent's seed and the position, so that evaluations are
+/// reproducible no matter which thread reaches a position first. Boards that are rotations or
+/// reflections of each other share a table entry, so they share a generator too.
+fn board_rng(game: &Game, params: &ExpectimaxParams) -> Rng {
+    Rng::new(params.seed ^ game.position_key())
+}
+
+/// The key of a position in the transposition table. Heuristics may count the score so far, and
+/// spawns depend on the rules, so both are part of the key along with the board. Position keys
+/// of boards of different sizes can be equal, so the size is too, up to rotation.
+fn table_key(game: &Game) -> u64 {
+    let board = game.get_board();
+    let size = (
+        board.width().min(board.height()),
+        board.width().max(board.height()),
+    );
+    let mut hasher = DefaultHasher::new();
+    (
+        size,
+        game.position_key(),
+        game.get_score(),
+        game.get_rules(),
+    )
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// One search from the root, with what it needs to share between branches.
+struct Search<'a> {
+    params: &'a ExpectimaxParams,
+    table: Option<&'a TranspositionTable>,
+    deadline: Option<Instant>,
+    // set once the deadline passed, after which every value is meaningless
+    aborted: AtomicBool,
+    // positions scored by the heuristic, and transposition table use, for reporting
+    evals: AtomicUsize,
+    lookups: AtomicUsize,
+    hits: AtomicUsize,
+}
+
+/// What a search did, for reporting.
+#[derive(Debug, Default, Clone, Copy)]
+struct SearchStats {
+    depth: usize,
+    elapsed: Duration,
+    evals: usize,
+    lookups: usize,
+    hits: usize,
 }
 
-struct RandHeuristic;
-impl GameHeuristic for RandHeuristic {
-    fn score(game: &Game, params: &ExpectimaxParams) -> f32 {
-        if let Some(seed) = params.seed {
-            fastrand::seed(seed);
+impl<'a> Search<'a> {
+    fn new(
+        params: &'a ExpectimaxParams,
+        table: Option<&'a TranspositionTable>,
+        deadline: Option<Instant>,
+    ) -> Self {
+        Search {
+            params,
+            table,
+            deadline,
+            aborted: AtomicBool::new(false),
+            evals: AtomicUsize::new(0),
+            lookups: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
         }
-        (0..params.heuristic_sims)
-            .into_iter()
-            .map(|_| *simulate_random_game(game.clone()).get_score() as f32)
-            .sum::<f32>()
-            / params.heuristic_sims as f32
     }
-}
 
-struct GameOverHeuristic;
-impl GameHeuristic for GameOverHeuristic {
-    fn score(game: &Game, params: &ExpectimaxParams) -> f32 {
-        let empty_tiles = empty_idxs(game).len();
-        if let Some(seed) = params.seed {
-            fastrand::seed(seed);
+    /// Whether the deadline passed. Once it has, the search unwinds as fast as it can.
+    fn aborted(&self) -> bool {
+        if self.aborted.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            self.aborted.store(true, Ordering::Relaxed);
+            return true;
         }
+        false
+    }
 
-        ((0..params.heuristic_sims)
-            .into_par_iter()
-            .map(|_| {
-                let rand_game = simulate_random_game(game.clone());
-                (empty_tiles * *rand_game.get_score()) as f32
-            })
-            .sum::<f32>())
-            / params.heuristic_sims as f32
+    fn stats(self, depth: usize, elapsed: Duration) -> SearchStats {
+        SearchStats {
+            depth,
+            elapsed,
+            evals: self.evals.into_inner(),
+            lookups: self.lookups.into_inner(),
+            hits: self.hits.into_inner(),
+        }
     }
-}
 
-fn empty_idxs(game: &Game) -> Vec<u8> {
-    game.get_state()
-        .iter()
-        .enumerate()
-        .filter(|(_, n)| **n == 0)
-        .map(|(i, _)| i as u8)
-        .collect::<Vec<_>>()
-}
+    fn heuristic(&self, game: &Game) -> f32 {
+        self.evals.fetch_add(1, Ordering::Relaxed);
+        self.params
+            .heuristic
+            .score(game, board_rng(game, self.params))
+    }
 
-/// starting from the expectation layer of the tree
-fn expectimax_recurse(game: &Game, params: &ExpectimaxParams) -> usize {
-    if params.curr_count() >= params.max_evals {
-        return GameOverHeuristic::score(&game, &params) as usize;
-    }
-
-    // expecti
-    // aka get a board state with each empty slot filled with a 2 or 4
-    let mut empty_idxs = empty_idxs(&game);
-    if game.game_over() {
-        return 0;
-    }
-    shuffle(&mut empty_idxs);
-
-    // for speed, let's limit the number of tiles we recurse on in the expectation portion.
-    // We expect that the expectation portion can be "fuzzier".
-    let idx_to_tile: Vec<(u8, u8, f32)> =
-        empty_idxs
-            .iter()
-            .take(params.num_tiles)
-            .fold(vec![], |mut acc, i| {
-                acc.push((*i, 1, 0.9));
-                acc.push((*i, 2, 0.1));
-                acc
-            });
-
-    let num_tiles = idx_to_tile.len();
-    let games_to_avg: Vec<(Game, f32)> = if num_tiles == 0 {
-        vec![(game.clone(), 1.0)]
-    } else {
-        idx_to_tile
-            .iter()
-            .map(|(idx, s, p)| {
-                // clone the game and set each tile to each of their expected values
-                let mut game = game.clone();
-                game.set_tile(idx % 4, idx / 4, *s);
-                (game, *p)
-            })
+    /// The value of an afterstate, where a tile is about to spawn: the average of every spawn,
+    /// weighted by its probability. `depth` is how many more moves may be searched, and
+    /// `probability` how likely the spawns leading here were.
+    fn chance(&self, game: &Game, depth: usize, probability: f32) -> f32 {
+        if depth == 0 || probability < self.params.min_probability {
+            return self.heuristic(game);
+        }
+        if self.aborted() {
+            return 0.0;
+        }
+
+        let key = table_key(game);
+        if let Some(table) = self.table {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            if let Some(value) = table.get(key, depth, probability) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return value;
+            }
+        }
+
+        let value = game
+            .spawn_outcomes()
             .collect::<Vec<_>>()
-    };
-
-    // map each possible expectation to that expectation's score
-    let list_of_expect_scores = games_to_avg
-        .par_iter()
-        .map(|(game, p)| {
-            let mut scores = MoveScores::default();
-            for m in game.available_moves() {
-                params.add_count();
-
-                let mut game = game.clone();
-                game.shift(m);
-                scores[m] = expectimax_recurse(&game, params);
+            .par_iter()
+            .map(|(game, p)| p * self.max(game, depth, probability * p))
+            .sum();
+        if let Some(table) = self.table {
+            // a value worked out after the deadline is missing some of its branches
+            if !self.aborted() {
+                table.insert(key, depth, probability, value);
+            }
+        }
+        value
+    }
+
+    /// The value of a position where a move is about to be played: the best of its moves.
+    fn max(&self, game: &Game, depth: usize, probability: f32) -> f32 {
+        if game.game_over() {
+            // a won game that can't go on is as good as it gets, so let the heuristic score it
+            if game.won() {
+                return self.heuristic(game);
             }
-            let max_score = *scores.values().max().unwrap() as f32;
-            max_score * p
-        })
-        .collect::<Vec<_>>();
-
-    // average the expectation scores and return it
-    let sum_scores: f32 = list_of_expect_scores.iter().sum();
-    (sum_scores / list_of_expect_scores.len() as f32) as usize
+            return 0.0;
+        }
+
+        game.available_moves()
+            .map(|m| self.chance(&game.afterstate(m).unwrap(), depth - 1, probability))
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
 }
 
 impl Expectimax {
@@ -169,55 +244,149 @@ impl Expectimax {
     where
         Self: Sized,
     {
-        Expectimax {
-            game,
-            params: ExpectimaxParams::default(),
-            last_scores: MoveScores::default(),
-        }
+        Expectimax::new_with(game, ExpectimaxParams::default())
     }
 
     pub fn new_seeded(seed: u64, game: Game) -> Self
     where
         Self: Sized,
     {
-        Expectimax {
+        Expectimax::new_with(
             game,
-            params: ExpectimaxParams {
-                seed: Some(seed),
+            ExpectimaxParams {
+                seed,
                 ..ExpectimaxParams::default()
             },
+        )
+    }
+
+    pub fn new_with(game: Game, params: ExpectimaxParams) -> Self
+    where
+        Self: Sized,
+    {
+        Expectimax {
+            game,
+            policy: ExpectimaxPolicy::new(params),
             last_scores: MoveScores::default(),
+            last_stats: SearchStats::default(),
+            last_outcome: None,
+            searched: Mutex::new(None),
         }
     }
 
-    fn expectimax(&self) -> MoveScores {
-        let mut scores = MoveScores::default();
-        let avail_moves = self.game.available_moves();
-        self.params.reset_count();
-        for m in avail_moves {
-            let mut game = self.game.clone();
-            game.shift(m);
-            let score = expectimax_recurse(&game, &self.params);
-            scores[m] = score;
+    /// Values every move of the game, searching only if the position wasn't searched already.
+    fn search(&self) -> (MoveValues, SearchStats) {
+        let mut searched = self.searched.lock().unwrap();
+        match *searched {
+            Some((game, values, stats)) if game == self.game => (values, stats),
+            _ => {
+                let (values, stats) = self.policy.expectimax(&self.game);
+                *searched = Some((self.game, values, stats));
+                (values, stats)
+            }
         }
-        scores
     }
 }
 
+/// How many positions the table of a search to `depth` should hold: every afterstate one search
+/// may store, four times over so that those of the last few moves are kept, up to `table_size`.
+fn table_capacity(params: &ExpectimaxParams) -> usize {
+    // a move and a spawn lead from an afterstate to at most this many others
+    let branching = 4 * MAX_SPAWN_KINDS * MAX_CELLS;
+    // afterstates searched to depth 0 are scored by the heuristic and never stored
+    let (mut level, mut total) = (4usize, 0usize);
+    for _ in 1..params.depth {
+        if total.saturating_mul(4) >= params.table_size {
+            break;
+        }
+        total = total.saturating_add(level);
+        level = level.saturating_mul(branching);
+    }
+    total.saturating_mul(4).min(params.table_size)
+}
+
+impl ExpectimaxPolicy {
+    /// # Panics
+    /// If `params.depth` is 0.
+    pub fn new(params: ExpectimaxParams) -> Self {
+        assert!(params.depth > 0, "expectimax must search at least one move");
+        ExpectimaxPolicy {
+            table: Some(table_capacity(&params))
+                .filter(|c| *c > 0)
+                .map(TranspositionTable::new),
+            params,
+        }
+    }
+
+    pub fn params(&self) -> &ExpectimaxParams {
+        &self.params
+    }
+
+    /// Values every move of a game, along with what the search did. Moves that aren't possible
+    /// are valued 0.
+    fn expectimax(&self, game: &Game) -> (MoveValues, SearchStats) {
+        let start = Instant::now();
+        let Some(budget) = self.params.time_budget else {
+            return self.search(game, self.params.depth, None, start).unwrap();
+        };
+
+        // the first iteration gets no deadline, so that there is always a move to play
+        let deadline = start + budget;
+        let mut best = self.search(game, 1, None, start).unwrap();
+        for depth in 2..=self.params.depth {
+            if Instant::now() >= deadline {
+                break;
+            }
+            match self.search(game, depth, Some(deadline), start) {
+                Some(result) => best = result,
+                None => break,
+            }
+        }
+        best
+    }
+
+    /// Values every move by searching `depth` moves ahead, or gives up if the deadline passes
+    /// first.
+    fn search(
+        &self,
+        game: &Game,
+        depth: usize,
+        deadline: Option<Instant>,
+        start: Instant,
+    ) -> Option<(MoveValues, SearchStats)> {
+        let search = Search::new(&self.params, self.table.as_ref(), deadline);
+        let mut values = MoveValues::default();
+        for m in game.available_moves() {
+            let afterstate = game.afterstate(m).unwrap();
+            values[m] = search.chance(&afterstate, depth - 1, 1.0);
+        }
+        if search.aborted() {
+            return None;
+        }
+        Some((values, search.stats(depth, start.elapsed())))
+    }
+}
+
+/// The value of every move. Moves are picked by these rather than by the rounded [`MoveScores`],
+/// so that values closer than 1 apart still tell moves apart.
+type MoveValues = EnumMap<Move, f32>;
+
+/// Rounds values down to scores, for display. Negative values score 0.
+fn to_scores(values: &MoveValues) -> MoveScores {
+    values.map(|_, v| v.max(0.0) as usize)
+}
+
 impl Agent for Expectimax {
     fn next_move(&self) -> Move {
-        let m = self.expectimax();
-        m.max_move()
+        let (values, _) = self.search();
+        values.max_move(&self.game)
     }
 
     fn make_move(&mut self) {
-        self.params = ExpectimaxParams {
-            seed: self.params.seed,
-            ..ExpectimaxParams::default()
-        };
-        let m = self.expectimax();
-        self.last_scores = m;
-        self.game.make_move(m.max_move());
+        let (values, stats) = self.search();
+        self.last_scores = to_scores(&values);
+        self.last_stats = stats;
+        self.last_outcome = Some(self.game.make_move(values.max_move(&self.game)));
     }
 
     fn get_game(&self) -> &Game {
@@ -225,18 +394,28 @@ impl Agent for Expectimax {
     }
 }
 
+impl Policy for ExpectimaxPolicy {
+    // the search draws its randomness from the seed and the board, so it leaves `rng` alone
+    fn decide(&self, game: &Game, _: &mut Rng) -> Decision {
+        let (values, _) = self.expectimax(game);
+        Decision {
+            direction: values.max_move(game),
+            scores: Some(to_scores(&values)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "Expectimax"
+    }
+}
+
 impl TuiAgent for Expectimax {
-    fn messages(&self) -> Vec<tui::text::Spans> {
-        let highest_move = self
-            .last_scores
-            .iter()
-            .max_by_key(|(_, score)| *score)
-            .unwrap()
-            .0;
+    fn messages(&self) -> Vec<tui::text::Spans<'_>> {
+        let played = self.last_outcome.map(|o| o.direction());
 
         let mut score_spans = Move::iter()
             .map(|m| {
-                if m == highest_move {
+                if Some(m) == played {
                     Spans::from(Span::styled(
                         format!("{}: {}", m, self.last_scores[m]),
                         Style::default()
@@ -255,12 +434,228 @@ impl TuiAgent for Expectimax {
                 Style::default().add_modifier(Modifier::BOLD),
             )),
             Spans::from(format!(
-                "Expectimax to determine the next best move. Approximately analyzing {} {} {} states per turn.",
-                self.params.curr_evals.try_lock().unwrap(), self.params.heuristic_sims, self.params.max_evals
+                "Expectimax to determine the next best move, looking {} moves ahead. Scored {} positions with {} last turn, in {:.2}s.",
+                self.last_stats.depth,
+                self.last_stats.evals,
+                self.policy.params.heuristic.name(),
+                self.last_stats.elapsed.as_secs_f32()
             )),
-            Spans::from(""),
         ];
+        if let Some(table) = &self.policy.table {
+            let stats = self.last_stats;
+            msgs.push(Spans::from(format!(
+                "Transposition table: {} of {} lookups hit ({:.1}%), {} of {} slots filled.",
+                stats.hits,
+                stats.lookups,
+                100.0 * stats.hits as f32 / stats.lookups.max(1) as f32,
+                table.len(),
+                table.capacity()
+            )));
+        }
+        msgs.push(Spans::from(""));
         msgs.append(&mut score_spans);
         msgs
     }
+
+    fn last_outcome(&self) -> Option<&MoveOutcome> {
+        self.last_outcome.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::heuristic::WeightedSum;
+    use crate::game::symmetry::Symmetry;
+
+    /// The game's score, so that values can be worked out by hand.
+    #[derive(Debug)]
+    struct Score;
+
+    impl Heuristic for Score {
+        fn score(&self, game: &Game, _: Rng) -> f32 {
+            *game.get_score() as f32
+        }
+    }
+
+    fn params(depth: usize) -> ExpectimaxParams {
+        ExpectimaxParams {
+            seed: 0,
+            depth,
+            heuristic: Arc::new(Score),
+            table_size: 0,
+            ..ExpectimaxParams::default()
+        }
+    }
+
+    #[test]
+    fn test_chance_weighting() {
+        let game = "2 2/. .".parse::<Game>().unwrap();
+        let (values, _) = ExpectimaxPolicy::new(params(2)).expectimax(&game);
+        // left leaves a 4 and scores 4, then a 2 or a 4 spawns in one of three cells. Only a 4
+        // beside or below the first one can merge, for another 8
+        let expected = (0.9 * 4.0 + 0.1 * 12.0) * 2.0 / 3.0 + (0.9 * 4.0 + 0.1 * 4.0) / 3.0;
+        assert!((values[Move::Left] - expected).abs() < 1e-4);
+        assert_eq!(values[Move::Up], 0.0);
+    }
+
+    #[test]
+    fn test_min_probability() {
+        let game = "2 4 8 16/. . 2 4/. . . 2/. . . .".parse::<Game>().unwrap();
+        let search = |depth, min_probability| {
+            let params = ExpectimaxParams {
+                min_probability,
+                ..params(depth)
+            };
+            ExpectimaxPolicy::new(params).expectimax(&game)
+        };
+        // every spawn after the first move is less likely than 1, so searching 3 deep is cut
+        // back to searching 2 deep
+        let (shallow, shallow_stats) = search(2, 1e-3);
+        let (cut, cut_stats) = search(3, 1.0);
+        let (_, full_stats) = search(3, 1e-3);
+        assert_eq!(cut, shallow);
+        assert_eq!(cut_stats.evals, shallow_stats.evals);
+        assert!(cut_stats.evals < full_stats.evals);
+    }
+
+    #[test]
+    fn test_table_key_size() {
+        // the same cells on a 4x4 and a 2x8 board are different positions
+        let square = "2 . . ./. . . ./. . . ./. . . .".parse::<Game>().unwrap();
+        let long = "2 ./. ./. ./. ./. ./. ./. ./. .".parse::<Game>().unwrap();
+        assert_eq!(square.position_key(), long.position_key());
+        assert_ne!(table_key(&square), table_key(&long));
+        assert_eq!(
+            table_key(&long),
+            table_key(&long.transform(Symmetry::Rotate90))
+        );
+    }
+
+    #[test]
+    fn test_only_legal_moves() {
+        // every line of play loses within two moves, so every move is worth 0, and right isn't
+        // possible on either board
+        for board in ["8 32 2/2 16 8/. 4 64", "4 16 2/4 32 4/16 128 2"] {
+            let game = board.parse::<Game>().unwrap();
+            let params = ExpectimaxParams {
+                seed: 0,
+                depth: 2,
+                ..ExpectimaxParams::default()
+            };
+            let policy = ExpectimaxPolicy::new(params.clone());
+            let (values, _) = policy.expectimax(&game);
+            assert!(values.values().all(|v| *v == 0.0));
+            let m = Expectimax::new_with(game, params).next_move();
+            assert!(game.can_move(m), "{} is not possible on {}", m, board);
+            let decision = policy.decide(&game, &mut Rng::new(0));
+            assert!(game.can_move(decision.direction));
+        }
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let game = "2 4 8 16/. . 2 4/. . . 2/. . . .".parse::<Game>().unwrap();
+        let weighted = |table_size| ExpectimaxParams {
+            depth: 3,
+            heuristic: Arc::new(WeightedSum::default()),
+            table_size,
+            ..params(3)
+        };
+        let (without, _) = ExpectimaxPolicy::new(weighted(0)).expectimax(&game);
+        let cached = ExpectimaxPolicy::new(weighted(1 << 12));
+        let (with, stats) = cached.expectimax(&game);
+        assert!(stats.hits > 0);
+        for m in Move::iter() {
+            assert!((with[m] - without[m]).abs() <= 1e-3 * without[m].abs().max(1.0));
+        }
+
+        // the same board with another score, as in another game, isn't read from the table
+        let game = "2 2/. .".parse::<Game>().unwrap();
+        let mut scored = game;
+        scored.make_move(Move::Left);
+        scored.set_state(&[1, 1, 0, 0]);
+        let params = ExpectimaxParams {
+            table_size: 1 << 12,
+            ..params(2)
+        };
+        let cached = ExpectimaxPolicy::new(params.clone());
+        cached.expectimax(&game);
+        let (fresh, _) = ExpectimaxPolicy::new(params).expectimax(&scored);
+        assert_eq!(cached.expectimax(&scored).0, fresh);
+    }
+
+    #[test]
+    fn test_table_capacity() {
+        let sized = |depth, table_size| {
+            table_capacity(&ExpectimaxParams {
+                table_size,
+                ..params(depth)
+            })
+        };
+        assert_eq!(sized(1, 1 << 18), 0);
+        assert!(sized(2, 1 << 18) < 100);
+        assert!(sized(3, 1 << 18) < sized(4, 1 << 18));
+        assert_eq!(sized(6, 1 << 18), 1 << 18);
+        assert_eq!(sized(usize::MAX, 1 << 18), 1 << 18);
+        assert_eq!(sized(3, 0), 0);
+    }
+
+    #[test]
+    fn test_searches_once_per_move() {
+        /// The game's score, counting the positions it scores.
+        #[derive(Debug, Default)]
+        struct Counted(AtomicUsize);
+
+        impl Heuristic for Counted {
+            fn score(&self, game: &Game, _: Rng) -> f32 {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                *game.get_score() as f32
+            }
+        }
+
+        let counted = Arc::new(Counted::default());
+        let params = ExpectimaxParams {
+            heuristic: counted.clone(),
+            ..params(2)
+        };
+        let mut agent = Expectimax::new_with(Game::new_seeded(1), params);
+        let m = agent.next_move();
+        let evals = counted.0.load(Ordering::Relaxed);
+        assert!(evals > 0);
+        assert_eq!(agent.next_move(), m);
+        agent.make_move();
+        assert_eq!(counted.0.load(Ordering::Relaxed), evals);
+        assert_eq!(agent.last_outcome().unwrap().direction(), m);
+
+        // the next position is searched afresh
+        agent.next_move();
+        assert!(counted.0.load(Ordering::Relaxed) > evals);
+    }
+
+    #[test]
+    fn test_symmetric_boards_share_rng() {
+        use crate::game::symmetry::Symmetry;
+
+        let game = "2 4 8 16/. . 2 4/. . . 2/. . . .".parse::<Game>().unwrap();
+        let params = params(1);
+        let draw = board_rng(&game, &params).u64();
+        for s in Symmetry::iter() {
+            assert_eq!(board_rng(&game.transform(s), &params).u64(), draw);
+        }
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let game = "2 4 8 16/. . 2 4/. . . 2/. . . .".parse::<Game>().unwrap();
+        let budgeted = ExpectimaxParams {
+            time_budget: Some(Duration::ZERO),
+            ..params(4)
+        };
+        let (values, stats) = ExpectimaxPolicy::new(budgeted.clone()).expectimax(&game);
+        assert_eq!(stats.depth, 1);
+        let (shallow, _) = ExpectimaxPolicy::new(params(1)).expectimax(&game);
+        assert_eq!(values, shallow);
+        assert!(game.can_move(Expectimax::new_with(game, budgeted).next_move()));
+    }
 }
@@ -0,0 +1,278 @@
+//! Position evaluation for search agents.
+//!
+//! A [`Heuristic`] scores a position, higher being better. There are two kinds: rollouts, which
+//! play random games from the position and score how they went, and [`WeightedSum`]s of classic
+//! board [`Feature`]s, which are deterministic and orders of magnitude faster.
+
+use std::{error::Error, fmt::Debug, path::Path};
+
+use enum_map::{enum_map, Enum, EnumMap};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+use crate::game::{
+    board::Board,
+    rng::Rng,
+    save::{read_ron, write_ron},
+    Game,
+};
+
+use super::random::simulate_random_game;
+
+pub trait Heuristic: Debug + Send + Sync {
+    /// Scores a position. Scores should not be negative, since a lost game scores 0. `rng` is
+    /// for heuristics that sample, and is the same every time a searcher evaluates the same
+    /// position, so that evaluations are reproducible.
+    ///
+    /// Searchers cache scores by position up to rotation and reflection, so a heuristic should
+    /// score every image of a position the same; one that doesn't gets whichever image was
+    /// scored first.
+    fn score(&self, game: &Game, rng: Rng) -> f32;
+
+    /// A short description for display, such as "10 random rollouts".
+    fn name(&self) -> String {
+        "a heuristic".to_string()
+    }
+}
+
+/// The average final score of random games played from the position.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RandHeuristic {
+    pub sims: usize,
+}
+
+impl Heuristic for RandHeuristic {
+    fn score(&self, game: &Game, mut rng: Rng) -> f32 {
+        (0..self.sims)
+            .map(|_| *simulate_random_game(*game, rng.u64()).get_score() as f32)
+            .sum::<f32>()
+            / self.sims as f32
+    }
+
+    fn name(&self) -> String {
+        format!("{} random rollouts", self.sims)
+    }
+}
+
+/// The average final score of random games played from the position, times the number of empty
+/// cells it has.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GameOverHeuristic {
+    pub sims: usize,
+}
+
+impl Heuristic for GameOverHeuristic {
+    fn score(&self, game: &Game, mut rng: Rng) -> f32 {
+        let empty_tiles = game.count_empty();
+        let seeds = (0..self.sims).map(|_| rng.u64()).collect::<Vec<_>>();
+
+        (seeds
+            .par_iter()
+            .map(|seed| {
+                let rand_game = simulate_random_game(*game, *seed);
+                (empty_tiles * *rand_game.get_score()) as f32
+            })
+            .sum::<f32>())
+            / self.sims as f32
+    }
+
+    fn name(&self) -> String {
+        format!("{} random rollouts", self.sims)
+    }
+}
+
+/// A property of a board that tends to make it easier or harder to play on. Features work on
+/// tile exponents, so a 2 counts as 1, a 4 as 2 and so on.
+#[derive(
+    Enum, EnumIter, Debug, PartialEq, Eq, Hash, Clone, Copy, Display, Serialize, Deserialize,
+)]
+pub enum Feature {
+    /// Number of empty cells.
+    EmptyCells,
+    /// How far rows and columns are from being sorted, in whichever direction each is closest
+    /// to, as a negative number. A board whose lines are all sorted scores 0.
+    Monotonicity,
+    /// How much neighbouring tiles differ, as a negative number. Empty cells are skipped.
+    Smoothness,
+    /// The exponent of the largest tile if one of the corners holds it, and 0 otherwise.
+    MaxInCorner,
+    /// Number of merges available, counting along rows and columns with empty cells skipped.
+    MergePotential,
+}
+
+impl Feature {
+    pub fn eval(self, board: &Board) -> f32 {
+        match self {
+            Feature::EmptyCells => board.count_empty() as f32,
+            Feature::Monotonicity => -lines(board)
+                .map(|line| {
+                    let (mut up, mut down) = (0, 0);
+                    for pair in line.windows(2) {
+                        if pair[0] < pair[1] {
+                            up += pair[1] - pair[0];
+                        } else {
+                            down += pair[0] - pair[1];
+                        }
+                    }
+                    up.min(down) as f32
+                })
+                .sum::<f32>(),
+            Feature::Smoothness => -lines(board)
+                .map(|line| {
+                    line.windows(2)
+                        .filter(|pair| pair[0] != 0 && pair[1] != 0)
+                        .map(|pair| pair[0].abs_diff(pair[1]) as f32)
+                        .sum::<f32>()
+                })
+                .sum::<f32>(),
+            Feature::MaxInCorner => {
+                let (w, h) = (board.width() - 1, board.height() - 1);
+                let max = board.max_exponent();
+                let corners = [(0, 0), (w, 0), (0, h), (w, h)];
+                if corners.iter().any(|(x, y)| board.get(*x, *y) == max) {
+                    max as f32
+                } else {
+                    0.0
+                }
+            }
+            Feature::MergePotential => lines(board)
+                .map(|line| {
+                    let tiles = line.into_iter().filter(|e| *e != 0).collect::<Vec<_>>();
+                    let mut merges = 0;
+                    let mut i = 0;
+                    while i + 1 < tiles.len() {
+                        if tiles[i] == tiles[i + 1] {
+                            merges += 1;
+                            i += 2;
+                        } else {
+                            i += 1;
+                        }
+                    }
+                    merges as f32
+                })
+                .sum(),
+        }
+    }
+
+    /// Every feature of a board.
+    pub fn eval_all(board: &Board) -> EnumMap<Feature, f32> {
+        enum_map! { f => f.eval(board) }
+    }
+}
+
+/// Every row, then every column, of a board, as tile exponents.
+fn lines(board: &Board) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let (w, h) = (board.width(), board.height());
+    let rows = (0..h).map(move |y| (0..w).map(|x| board.get(x, y)).collect());
+    let columns = (0..w).map(move |x| (0..h).map(|y| board.get(x, y)).collect());
+    rows.chain(columns)
+}
+
+/// A weighted sum of board features, plus a constant bias.
+///
+/// Some features are negative, so the bias keeps positions that are still in play scoring above
+/// lost ones, which score 0. As a [`Heuristic`], sums below 0 score 0, however the weights are
+/// set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightedSum {
+    pub bias: f32,
+    pub weights: EnumMap<Feature, f32>,
+}
+
+impl WeightedSum {
+    pub fn new(weights: EnumMap<Feature, f32>) -> Self {
+        WeightedSum {
+            bias: 1000.0,
+            weights,
+        }
+    }
+
+    /// A sum of a single feature.
+    pub fn only(feature: Feature) -> Self {
+        let mut weights = EnumMap::default();
+        weights[feature] = 1.0;
+        WeightedSum::new(weights)
+    }
+
+    /// Writes the weights to a RON file. Missing parent directories are created.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_ron(self, path.as_ref())
+    }
+
+    /// Reads weights written by [`WeightedSum::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<WeightedSum, Box<dyn Error>> {
+        read_ron(path.as_ref())
+    }
+
+    pub fn eval(&self, board: &Board) -> f32 {
+        self.bias
+            + Feature::iter()
+                .filter(|f| self.weights[*f] != 0.0)
+                .map(|f| self.weights[f] * f.eval(board))
+                .sum::<f32>()
+    }
+}
+
+impl Default for WeightedSum {
+    /// Hand-picked weights that play well enough with a shallow search.
+    fn default() -> Self {
+        WeightedSum::new(enum_map! {
+            Feature::EmptyCells => 2.7,
+            Feature::Monotonicity => 1.0,
+            Feature::Smoothness => 0.1,
+            Feature::MaxInCorner => 1.0,
+            Feature::MergePotential => 0.7,
+        })
+    }
+}
+
+impl Heuristic for WeightedSum {
+    fn score(&self, game: &Game, _: Rng) -> f32 {
+        self.eval(game.get_board()).max(0.0)
+    }
+
+    fn name(&self) -> String {
+        "weighted board features".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features() {
+        let board = "2 4 8 16/. . 2 4/. . . 2/. . . .".parse::<Board>().unwrap();
+        assert_eq!(Feature::EmptyCells.eval(&board), 9.0);
+        assert_eq!(Feature::Monotonicity.eval(&board), 0.0);
+        assert_eq!(Feature::Smoothness.eval(&board), -9.0);
+        assert_eq!(Feature::MaxInCorner.eval(&board), 4.0);
+        assert_eq!(Feature::MergePotential.eval(&board), 0.0);
+
+        let board = "2 . 2 4/4 . . 4/. . . ./. . . 8".parse::<Board>().unwrap();
+        assert_eq!(Feature::Monotonicity.eval(&board), -6.0);
+        assert_eq!(Feature::MergePotential.eval(&board), 3.0);
+        assert_eq!(Feature::MaxInCorner.eval(&board), 3.0);
+        assert_eq!(
+            Feature::MaxInCorner.eval(&"2 . ./. 8 .".parse().unwrap()),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_weighted_sum() {
+        let board = "2 4 8 16/. . 2 4/. . . 2/. . . .".parse::<Board>().unwrap();
+        let sum = WeightedSum::only(Feature::EmptyCells);
+        assert_eq!(sum.eval(&board), sum.bias + 9.0);
+
+        let mut sum = WeightedSum::only(Feature::EmptyCells);
+        sum.bias = -100.0;
+        assert_eq!(sum.score(&Game::new(), Rng::new(0)), 0.0);
+
+        let sum = WeightedSum::default();
+        let s = ron::to_string(&sum).unwrap();
+        assert_eq!(ron::from_str::<WeightedSum>(&s).unwrap(), sum);
+    }
+}
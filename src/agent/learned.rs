@@ -0,0 +1,238 @@
+//! A value function learned from self-play with linfa.
+//!
+//! Agents play games, every position they reach is described by a few numbers (the board
+//! [`Feature`]s and how many tiles of each size it holds), and a ridge regression fitted with
+//! linfa-elasticnet learns how much more score a game goes on to make from positions like it.
+//! The fitted [`LinearValue`] is a [`Heuristic`], so it can score the leaves of an
+//! [`Expectimax`](super::expectimax::Expectimax) search in place of random rollouts.
+
+use std::{error::Error, path::Path};
+
+use linfa::{
+    traits::{Fit, PredictInplace},
+    Dataset,
+};
+use linfa_elasticnet::{ElasticNet, ElasticNetError};
+use ndarray::{Array1, Array2, ArrayBase, Axis, Data, Ix1, Ix2};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    eval,
+    game::{
+        board::{Board, MAX_EXPONENT},
+        rng::Rng,
+        save::{read_ron, write_ron},
+        Game,
+    },
+};
+
+use super::{
+    heuristic::{Feature, Heuristic},
+    Agent,
+};
+
+/// Number of values [`board_features`] describes a board with.
+pub const NUM_FEATURES: usize = 5 + MAX_EXPONENT as usize;
+
+/// The board features, followed by the number of tiles of each exponent from 1 to 15.
+pub fn board_features(board: &Board) -> [f64; NUM_FEATURES] {
+    let mut features = [0.0; NUM_FEATURES];
+    for (i, f) in Feature::iter().enumerate() {
+        features[i] = f.eval(board) as f64;
+    }
+    for e in board.tiles().into_iter().filter(|e| *e != 0) {
+        features[Feature::iter().len() + e as usize - 1] += 1.0;
+    }
+    features
+}
+
+/// Plays `games` games with agents built by `new_agent` from a game and a seed, and records
+/// every position they reach with the score the game went on to make from it.
+pub fn generate_dataset<A: Agent>(
+    games: usize,
+    seed: u64,
+    new_agent: impl Fn(Game, u64) -> A + Sync,
+) -> Dataset<f64, f64, Ix1> {
+    let mut rng = Rng::new(seed);
+    let seeds = (0..games).map(|_| rng.u64()).collect::<Vec<_>>();
+    let samples = seeds
+        .par_iter()
+        .flat_map_iter(|seed| {
+            let mut agent = new_agent(Game::new_seeded(*seed), eval::agent_seed(*seed));
+            let mut positions = vec![];
+            while !agent.get_game().game_over() {
+                agent.make_move();
+                let game = agent.get_game();
+                positions.push((board_features(game.get_board()), *game.get_score()));
+            }
+            let final_score = *agent.get_game().get_score();
+            positions
+                .into_iter()
+                .map(move |(features, score)| (features, (final_score - score) as f64))
+        })
+        .collect::<Vec<_>>();
+
+    let records = Array2::from_shape_fn((samples.len(), NUM_FEATURES), |(i, j)| samples[i].0[j]);
+    let targets = samples.iter().map(|(_, t)| *t).collect::<Array1<_>>();
+    Dataset::new(records, targets)
+}
+
+/// Linear least squares with an L2 penalty on the weights, fitted by linfa's elastic net with
+/// no L1 part. Records are standardized before fitting, so the penalty treats every feature
+/// alike.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RidgeRegression {
+    pub alpha: f64,
+}
+
+impl Default for RidgeRegression {
+    fn default() -> Self {
+        RidgeRegression { alpha: 1e-2 }
+    }
+}
+
+/// A fitted linear model: `intercept + weights . ((x - means) / scales)`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinearValue {
+    means: Vec<f64>,
+    scales: Vec<f64>,
+    weights: Vec<f64>,
+    intercept: f64,
+}
+
+impl<D: Data<Elem = f64>> Fit<ArrayBase<D, Ix2>, Array1<f64>, ElasticNetError> for RidgeRegression {
+    type Object = LinearValue;
+
+    fn fit(
+        &self,
+        dataset: &linfa::DatasetBase<ArrayBase<D, Ix2>, Array1<f64>>,
+    ) -> Result<LinearValue, ElasticNetError> {
+        let x = dataset.records();
+        let y = dataset.targets();
+        if x.nrows() == 0 {
+            return Err(ElasticNetError::NotEnoughSamples);
+        }
+        if y.len() != x.nrows() {
+            return Err(linfa::Error::MismatchedShapes(x.nrows(), y.len()).into());
+        }
+
+        let means = x.mean_axis(Axis(0)).unwrap();
+        // constant features get a scale of 1, and end up with no weight
+        let scales = x
+            .std_axis(Axis(0), 0.0)
+            .mapv(|s| if s > 1e-12 { s } else { 1.0 });
+        // targets are scaled too, so the tolerance on the weights does not depend on the score
+        let y_scale = y.std(0.0).max(1e-12);
+        let standardized = Dataset::new((x - &means) / &scales, y / y_scale);
+
+        let model = ElasticNet::params()
+            .penalty(self.alpha)
+            .l1_ratio(0.0)
+            .tolerance(1e-6)
+            .fit(&standardized)?;
+
+        Ok(LinearValue {
+            means: means.to_vec(),
+            scales: scales.to_vec(),
+            weights: (model.hyperplane() * y_scale).to_vec(),
+            intercept: model.intercept() * y_scale,
+        })
+    }
+}
+
+impl LinearValue {
+    /// The predicted score still to be made from a position with these features.
+    pub fn predict_one(&self, features: &[f64]) -> f64 {
+        self.intercept
+            + features
+                .iter()
+                .zip(&self.means)
+                .zip(&self.scales)
+                .zip(&self.weights)
+                .map(|(((x, m), s), w)| w * (x - m) / s)
+                .sum::<f64>()
+    }
+
+    /// Writes the model to a RON file. Missing parent directories are created.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_ron(self, path.as_ref())
+    }
+
+    /// Reads a model written by [`LinearValue::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<LinearValue, Box<dyn Error>> {
+        read_ron(path.as_ref())
+    }
+}
+
+impl<D: Data<Elem = f64>> PredictInplace<ArrayBase<D, Ix2>, Array1<f64>> for LinearValue {
+    fn predict_inplace(&self, x: &ArrayBase<D, Ix2>, y: &mut Array1<f64>) {
+        assert_eq!(x.nrows(), y.len(), "one target per record");
+        for (row, y) in x.rows().into_iter().zip(y.iter_mut()) {
+            *y = self.predict_one(row.as_slice().unwrap_or(&row.to_vec()));
+        }
+    }
+
+    fn default_target(&self, x: &ArrayBase<D, Ix2>) -> Array1<f64> {
+        Array1::zeros(x.nrows())
+    }
+}
+
+impl Heuristic for LinearValue {
+    /// The score made so far plus the score the model expects is still to come.
+    fn score(&self, game: &Game, _: Rng) -> f32 {
+        let remaining = self.predict_one(&board_features(game.get_board())).max(0.0);
+        (*game.get_score() as f64 + remaining) as f32
+    }
+
+    fn name(&self) -> String {
+        "a learned value function".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use linfa::traits::Predict;
+    use ndarray::array;
+
+    use super::*;
+    use crate::agent::random::RandomAgent;
+
+    #[test]
+    fn test_ridge_regression() {
+        let x = array![[1.0, 0.0], [2.0, 1.0], [3.0, 0.0], [4.0, 1.0], [5.0, 5.0]];
+        let y = x
+            .rows()
+            .into_iter()
+            .map(|r| 3.0 + 2.0 * r[0] - r[1])
+            .collect::<Array1<_>>();
+        let dataset = Dataset::new(x, y.clone());
+
+        let model = RidgeRegression { alpha: 0.0 }.fit(&dataset).unwrap();
+        let predicted: Array1<f64> = model.predict(dataset.records());
+        for (p, t) in predicted.iter().zip(&y) {
+            assert!((p - t).abs() < 1e-3, "{} != {}", p, t);
+        }
+
+        // the penalty shrinks the weights
+        let exact = model.weights.iter().map(|w| w * w).sum::<f64>();
+        let model = RidgeRegression { alpha: 10.0 }.fit(&dataset).unwrap();
+        assert!(model.weights.iter().map(|w| w * w).sum::<f64>() < exact);
+        assert!(RidgeRegression { alpha: -1.0 }.fit(&dataset).is_err());
+    }
+
+    #[test]
+    fn test_learn_from_self_play() {
+        let dataset = generate_dataset(4, 1, |game, seed| RandomAgent::new_seeded(seed, game));
+        assert_eq!(dataset.records().ncols(), NUM_FEATURES);
+        assert!(dataset.records().nrows() > 0);
+
+        let model = RidgeRegression::default().fit(&dataset).unwrap();
+        let s = ron::to_string(&model).unwrap();
+        assert_eq!(ron::from_str::<LinearValue>(&s).unwrap(), model);
+
+        let game = Game::new_seeded(2);
+        assert!(model.score(&game, Rng::new(0)) >= 0.0);
+    }
+}
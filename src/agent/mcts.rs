@@ -0,0 +1,474 @@
+//! Monte Carlo tree search with UCT.
+//!
+//! The tree alternates between decision nodes, where a move is picked by UCT, and chance nodes,
+//! where a tile spawns. Chance nodes are not expanded in full: each visit samples a spawn, and
+//! the positions it leads to are added to the tree as they come up, so likely spawns end up
+//! searched the most.
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use enum_map::EnumMap;
+use strum::IntoEnumIterator;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::game::{board::Board, outcome::MoveOutcome, rng::Rng, Game, Move};
+
+use super::{heuristic::Heuristic, random::simulate_random_game, Agent, MaxMove, TuiAgent};
+
+/// How a position added to the tree is scored.
+#[derive(Debug, Clone)]
+pub enum Rollout {
+    /// Play random moves until the game ends, and use the score gained.
+    Random,
+    /// Play the move that scores the most right away until the game ends, breaking ties at
+    /// random, and use the score gained.
+    Greedy,
+    /// Don't play on: use the score gained so far plus the heuristic's score of the position.
+    Heuristic(Arc<dyn Heuristic>),
+}
+
+impl fmt::Display for Rollout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rollout::Random => write!(f, "random"),
+            Rollout::Greedy => write!(f, "greedy"),
+            Rollout::Heuristic(_) => write!(f, "heuristic"),
+        }
+    }
+}
+
+/// When to stop searching and play a move.
+#[derive(Debug, Clone, Copy)]
+pub enum Budget {
+    Iterations(usize),
+    /// Search for this long each move. Searches are then no longer reproducible.
+    Time(Duration),
+}
+
+#[derive(Debug, Clone)]
+pub struct MctsParams {
+    pub seed: u64,
+    /// Weight of the exploration term of UCT. Values are scaled to `[0, 1]` before they are
+    /// compared, so this does not depend on how high scores get.
+    pub exploration: f32,
+    pub rollout: Rollout,
+    pub budget: Budget,
+}
+
+impl Default for MctsParams {
+    fn default() -> Self {
+        MctsParams {
+            seed: fastrand::u64(..),
+            exploration: 1.0,
+            rollout: Rollout::Random,
+            budget: Budget::Iterations(2000),
+        }
+    }
+}
+
+struct Decision {
+    game: Game,
+    visits: usize,
+    // the chance node each tried move leads to
+    children: EnumMap<Move, Option<usize>>,
+    untried: Vec<Move>,
+}
+
+struct Chance {
+    parent: usize,
+    direction: Move,
+    visits: usize,
+    total: f64,
+    // the positions sampled so far, with their decision node
+    children: Vec<(Board, usize)>,
+}
+
+/// The statistics of a root move after a search.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MoveStats {
+    pub visits: usize,
+    /// Mean score gained after the move, over its visits.
+    pub value: f64,
+}
+
+struct Tree<'a> {
+    params: &'a MctsParams,
+    rng: Rng,
+    decisions: Vec<Decision>,
+    chances: Vec<Chance>,
+    // score of the root, so that values are the score gained from it
+    root_score: usize,
+    // the highest value seen, to scale values for UCT
+    max_value: f64,
+}
+
+impl<'a> Tree<'a> {
+    fn new(game: &Game, params: &'a MctsParams, rng: Rng) -> Self {
+        let root_score = *game.get_score();
+        let mut tree = Tree {
+            params,
+            rng,
+            decisions: vec![],
+            chances: vec![],
+            root_score,
+            max_value: 0.0,
+        };
+        tree.add_decision(*game);
+        tree
+    }
+
+    fn add_decision(&mut self, game: Game) -> usize {
+        let mut untried = game.available_moves().collect::<Vec<_>>();
+        if game.game_over() {
+            untried.clear();
+        }
+        self.decisions.push(Decision {
+            game,
+            visits: 0,
+            children: EnumMap::default(),
+            untried,
+        });
+        self.decisions.len() - 1
+    }
+
+    fn add_chance(&mut self, parent: usize, direction: Move) -> usize {
+        self.chances.push(Chance {
+            parent,
+            direction,
+            visits: 0,
+            total: 0.0,
+            children: vec![],
+        });
+        let chance = self.chances.len() - 1;
+        self.decisions[parent].children[direction] = Some(chance);
+        chance
+    }
+
+    /// Plays the chance node's move with a fresh spawn, returning the decision node reached and
+    /// whether it is new.
+    fn sample(&mut self, chance: usize) -> (usize, bool) {
+        let Chance {
+            parent, direction, ..
+        } = self.chances[chance];
+        let mut game = self.decisions[parent].game;
+        game.set_rng(self.rng.split());
+        game.make_move(direction);
+
+        let board = *game.get_board();
+        if let Some((_, d)) = self.chances[chance]
+            .children
+            .iter()
+            .find(|(b, _)| *b == board)
+        {
+            return (*d, false);
+        }
+        let d = self.add_decision(game);
+        self.chances[chance].children.push((board, d));
+        (d, true)
+    }
+
+    /// The tried move of a fully expanded decision node with the highest UCT score.
+    fn select(&self, decision: usize) -> usize {
+        let node = &self.decisions[decision];
+        let ln_visits = (node.visits.max(1) as f64).ln();
+        let scale = self.max_value.max(1.0);
+        node.children
+            .values()
+            .flatten()
+            .map(|c| {
+                let chance = &self.chances[*c];
+                let mean = chance.total / chance.visits as f64 / scale;
+                let explore = (ln_visits / chance.visits as f64).sqrt();
+                (*c, mean + self.params.exploration as f64 * explore)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0
+    }
+
+    /// Scores a position newly added to the tree, as score gained from the root.
+    fn rollout(&mut self, decision: usize) -> f64 {
+        let game = &self.decisions[decision].game;
+        let seed = self.rng.u64();
+        let end = match &self.params.rollout {
+            Rollout::Random => *simulate_random_game(*game, seed).get_score(),
+            Rollout::Greedy => *greedy_game(*game, seed).get_score(),
+            Rollout::Heuristic(h) => {
+                // a lost game is worth only what it scored
+                let value = if game.game_over() {
+                    0.0
+                } else {
+                    h.score(game, Rng::new(seed))
+                };
+                return (*game.get_score() - self.root_score) as f64 + value as f64;
+            }
+        };
+        (end - self.root_score) as f64
+    }
+
+    /// Runs one iteration: walks down the tree, adds a position, scores it and backs the score
+    /// up.
+    fn iterate(&mut self) {
+        let mut decision = 0;
+        let mut path = vec![];
+        let value = loop {
+            self.decisions[decision].visits += 1;
+            if self.decisions[decision]
+                .children
+                .values()
+                .all(Option::is_none)
+                && self.decisions[decision].untried.is_empty()
+            {
+                // the game is over here
+                break (*self.decisions[decision].game.get_score() - self.root_score) as f64;
+            }
+
+            let chance = if self.decisions[decision].untried.is_empty() {
+                self.select(decision)
+            } else {
+                let untried = &mut self.decisions[decision].untried;
+                let m = untried.swap_remove(self.rng.usize(0..untried.len()));
+                self.add_chance(decision, m)
+            };
+            path.push(chance);
+
+            let (next, is_new) = self.sample(chance);
+            decision = next;
+            if is_new {
+                self.decisions[decision].visits += 1;
+                break self.rollout(decision);
+            }
+        };
+
+        self.max_value = self.max_value.max(value);
+        for chance in path {
+            self.chances[chance].visits += 1;
+            self.chances[chance].total += value;
+        }
+    }
+
+    fn root_stats(&self) -> EnumMap<Move, MoveStats> {
+        let mut stats = EnumMap::<Move, MoveStats>::default();
+        for (m, c) in &self.decisions[0].children {
+            if let Some(c) = c {
+                let chance = &self.chances[*c];
+                stats[m] = MoveStats {
+                    visits: chance.visits,
+                    value: chance.total / chance.visits.max(1) as f64,
+                };
+            }
+        }
+        stats
+    }
+}
+
+/// Plays a game to the end, always picking the move that scores the most right away.
+fn greedy_game(mut game: Game, seed: u64) -> Game {
+    let mut rng = Rng::new(seed);
+    game.set_rng(rng.split());
+    while !game.game_over() {
+        let moves = game
+            .available_moves()
+            .map(|m| (m, game.get_board().shift(m).1))
+            .collect::<Vec<_>>();
+        let best = moves.iter().map(|(_, s)| *s).max().unwrap();
+        let best = moves.iter().filter(|(_, s)| *s == best).collect::<Vec<_>>();
+        game.make_move(best[rng.usize(0..best.len())].0);
+    }
+    game
+}
+
+pub struct Mcts {
+    game: Game,
+    params: MctsParams,
+    rng: Rng,
+    last_stats: EnumMap<Move, MoveStats>,
+    last_iterations: usize,
+    last_outcome: Option<MoveOutcome>,
+}
+
+impl Mcts {
+    pub fn new(game: Game) -> Self {
+        Mcts::new_with(game, MctsParams::default())
+    }
+
+    pub fn new_seeded(seed: u64, game: Game) -> Self {
+        Mcts::new_with(
+            game,
+            MctsParams {
+                seed,
+                ..MctsParams::default()
+            },
+        )
+    }
+
+    pub fn new_with(game: Game, params: MctsParams) -> Self {
+        Mcts {
+            game,
+            rng: Rng::new(params.seed),
+            params,
+            last_stats: EnumMap::default(),
+            last_iterations: 0,
+            last_outcome: None,
+        }
+    }
+
+    /// Searches from the current position, returning the statistics of each root move and the
+    /// number of iterations run.
+    fn search(&self, rng: Rng) -> (EnumMap<Move, MoveStats>, usize) {
+        let mut tree = Tree::new(&self.game, &self.params, rng);
+        let mut iterations = 0;
+        match self.params.budget {
+            Budget::Iterations(n) => {
+                for _ in 0..n {
+                    tree.iterate();
+                }
+                iterations = n;
+            }
+            Budget::Time(budget) => {
+                let start = Instant::now();
+                while iterations == 0 || start.elapsed() < budget {
+                    tree.iterate();
+                    iterations += 1;
+                }
+            }
+        }
+        (tree.root_stats(), iterations)
+    }
+}
+
+/// The most visited of the moves possible in `game`, which is the one the search trusts most.
+///
+/// # Panics
+/// If the game is over.
+fn most_visited(stats: &EnumMap<Move, MoveStats>, game: &Game) -> Move {
+    stats.map(|_, s| s.visits).max_move(game)
+}
+
+impl Agent for Mcts {
+    // peeks at the agent's stream, so this is the move `make_move` will play
+    fn next_move(&self) -> Move {
+        let mut rng = self.rng;
+        let (stats, _) = self.search(rng.split());
+        most_visited(&stats, &self.game)
+    }
+
+    fn make_move(&mut self) {
+        let rng = self.rng.split();
+        let (stats, iterations) = self.search(rng);
+        self.last_stats = stats;
+        self.last_iterations = iterations;
+        self.last_outcome = Some(self.game.make_move(most_visited(&stats, &self.game)));
+    }
+
+    fn get_game(&self) -> &Game {
+        &self.game
+    }
+}
+
+impl TuiAgent for Mcts {
+    fn messages(&self) -> Vec<Spans<'_>> {
+        // the game may be over by now, so the move is picked among those searched
+        let highest_move = Move::iter()
+            .filter(|m| self.last_stats[*m].visits > 0)
+            .max_by_key(|m| self.last_stats[*m].visits);
+
+        let mut stat_spans = Move::iter()
+            .map(|m| {
+                let stats = self.last_stats[m];
+                let text = format!("{}: {} visits, {:.0} mean", m, stats.visits, stats.value);
+                if Some(m) == highest_move {
+                    Spans::from(Span::styled(
+                        text,
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .bg(Color::Green),
+                    ))
+                } else {
+                    Spans::from(text)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut msgs = vec![
+            Spans::from(Span::styled(
+                "Monte Carlo Tree Search",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Spans::from(format!(
+                "Growing a tree by UCT with exploration {} and {} rollouts. Ran {} iterations last turn, playing the most visited move.",
+                self.params.exploration, self.params.rollout, self.last_iterations
+            )),
+            Spans::from(""),
+        ];
+        msgs.append(&mut stat_spans);
+        msgs
+    }
+
+    fn last_outcome(&self) -> Option<&MoveOutcome> {
+        self.last_outcome.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::heuristic::WeightedSum;
+
+    #[test]
+    fn test_search() {
+        for rollout in [
+            Rollout::Random,
+            Rollout::Greedy,
+            Rollout::Heuristic(Arc::new(WeightedSum::default())),
+        ] {
+            let params = MctsParams {
+                seed: 5,
+                rollout,
+                budget: Budget::Iterations(200),
+                ..MctsParams::default()
+            };
+            let agent = Mcts::new_with(Game::new_seeded(5), params);
+            let (stats, iterations) = agent.search(Rng::new(1));
+            assert_eq!(iterations, 200);
+            assert_eq!(stats.values().map(|s| s.visits).sum::<usize>(), 200);
+            for m in Move::iter() {
+                assert_eq!(stats[m].visits > 0, agent.get_game().can_move(m));
+            }
+            assert_eq!(agent.next_move(), agent.next_move());
+        }
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let params = MctsParams {
+            seed: 5,
+            budget: Budget::Time(Duration::from_millis(20)),
+            ..MctsParams::default()
+        };
+        let agent = Mcts::new_with(Game::new_seeded(5), params);
+        let (stats, iterations) = agent.search(Rng::new(1));
+        assert!(iterations > 0);
+        assert_eq!(stats.values().map(|s| s.visits).sum::<usize>(), iterations);
+        assert!(agent
+            .get_game()
+            .can_move(most_visited(&stats, agent.get_game())));
+    }
+
+    #[test]
+    fn test_most_visited_is_possible() {
+        // only left and right are possible, and no move has been visited
+        let game = "2 2 4 8/4 8 16 32/8 16 32 64/16 32 64 128"
+            .parse::<Game>()
+            .unwrap();
+        let m = most_visited(&EnumMap::default(), &game);
+        assert!(game.can_move(m));
+    }
+}
@@ -3,34 +3,63 @@ use enum_map::EnumMap;
 use tui::text::Spans;
 
 use crate::{
-    game::{Game, Move},
+    game::{history::RecordedGame, outcome::MoveOutcome, Game, Move},
     tui::IntAction,
 };
 
+pub mod corner;
 pub mod expectimax;
+pub mod heuristic;
+pub mod learned;
+pub mod mcts;
+pub mod ntuple;
+pub mod policy;
 pub mod random;
+pub mod spec;
+pub mod transposition;
+pub mod tuning;
 pub mod user;
 
 pub trait Agent {
     fn get_game(&self) -> &Game;
     fn next_move(&self) -> Move;
-    fn make_move(&mut self); 
+    fn make_move(&mut self);
 }
 
 pub trait TuiAgent: Agent {
     fn get_input(&mut self, _: &Event) -> IntAction {
         IntAction::Continue
     }
-    fn messages(&self) -> Vec<Spans>;
+    fn messages(&self) -> Vec<Spans<'_>>;
+    /// The outcome of the last move played, so the board can highlight what changed.
+    fn last_outcome(&self) -> Option<&MoveOutcome> {
+        None
+    }
+    /// The game with the moves that can be undone, for agents that keep them, so that saving
+    /// the game keeps them too.
+    fn recorded_game(&self) -> Option<&RecordedGame> {
+        None
+    }
 }
 
 pub type MoveScores = EnumMap<Move, usize>;
 pub trait MaxMove {
-    fn max_move(&self) -> Move;
+    /// The best scoring of the moves possible in `game`. Moves that aren't possible are skipped
+    /// whatever they score, since playing one would leave the game stuck.
+    ///
+    /// # Panics
+    /// If the game is over.
+    fn max_move(&self, game: &Game) -> Move;
 }
 
-impl MaxMove for MoveScores {
-    fn max_move(&self) -> Move {
-        self.iter().max_by_key(|(_, score)| *score).unwrap().0
+impl<T: PartialOrd + Copy> MaxMove for EnumMap<Move, T> {
+    fn max_move(&self, game: &Game) -> Move {
+        game.available_moves()
+            .max_by(|a, b| {
+                self[*a]
+                    .partial_cmp(&self[*b])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .expect("no move is possible in a game that is over")
     }
 }
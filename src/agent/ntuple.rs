@@ -0,0 +1,499 @@
+//! An agent driven by an n-tuple network: a value function over afterstates made of lookup
+//! tables, one per tuple of cells, learned from self-play by temporal difference learning.
+//!
+//! Each tuple reads the tiles on a few cells and uses them as an index into its table. The value
+//! of a board is the sum of the tables over every tuple, read on every symmetry of the board, so
+//! that positions that are rotations or reflections of each other are valued the same.
+
+use std::{error::Error, fmt, path::Path, sync::Arc};
+
+use enum_map::EnumMap;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::game::{
+    board::{check_size, Board},
+    outcome::MoveOutcome,
+    rng::Rng,
+    save::{read_bincode, write_bincode},
+    symmetry::Symmetry,
+    Game, Move,
+};
+
+use super::{heuristic::Heuristic, Agent, MaxMove, TuiAgent};
+
+/// The longest tuple a network may have. Each tuple has a table of 16^len weights.
+pub const MAX_TUPLE_LEN: usize = 6;
+
+/// Rows, and squares of four cells, for a 4x4 board. Columns and the other squares are covered
+/// by symmetry.
+pub const DEFAULT_TUPLES: &[&[(usize, usize)]] = &[
+    &[(0, 0), (1, 0), (2, 0), (3, 0)],
+    &[(0, 1), (1, 1), (2, 1), (3, 1)],
+    &[(0, 0), (1, 0), (0, 1), (1, 1)],
+    &[(1, 0), (2, 0), (1, 1), (2, 1)],
+    &[(1, 1), (2, 1), (1, 2), (2, 2)],
+];
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "NTupleRepr", into = "NTupleRepr")]
+pub struct NTupleNetwork {
+    width: usize,
+    height: usize,
+    tuples: Vec<Vec<(usize, usize)>>,
+    weights: Vec<Vec<f32>>,
+    // for every tuple under every symmetry: the tuple, and the cell indexes it reads
+    patterns: Vec<(usize, Vec<usize>)>,
+}
+
+/// The serialized form of a network: its tuples and their tables. Patterns are rebuilt on load.
+#[derive(Serialize, Deserialize)]
+struct NTupleRepr {
+    width: usize,
+    height: usize,
+    tuples: Vec<Vec<(usize, usize)>>,
+    weights: Vec<Vec<f32>>,
+}
+
+impl From<NTupleNetwork> for NTupleRepr {
+    fn from(network: NTupleNetwork) -> Self {
+        NTupleRepr {
+            width: network.width,
+            height: network.height,
+            tuples: network.tuples,
+            weights: network.weights,
+        }
+    }
+}
+
+impl TryFrom<NTupleRepr> for NTupleNetwork {
+    type Error = String;
+
+    fn try_from(repr: NTupleRepr) -> Result<Self, Self::Error> {
+        let tuples = repr.tuples.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let mut network = NTupleNetwork::new(repr.width, repr.height, &tuples)?;
+        for (table, weights) in network.weights.iter_mut().zip(repr.weights) {
+            if weights.len() != table.len() {
+                return Err(format!(
+                    "expected {} weights for a tuple, found {}",
+                    table.len(),
+                    weights.len()
+                ));
+            }
+            *table = weights;
+        }
+        Ok(network)
+    }
+}
+
+impl fmt::Debug for NTupleNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "NTupleNetwork({}x{}, {} tuples)",
+            self.width,
+            self.height,
+            self.tuples.len()
+        )
+    }
+}
+
+impl NTupleNetwork {
+    /// A network of all-zero weights over the given tuples of `(x, y)` cells.
+    pub fn new(width: usize, height: usize, tuples: &[&[(usize, usize)]]) -> Result<Self, String> {
+        check_size(width, height)?;
+        if let Some(t) = tuples
+            .iter()
+            .find(|t| t.is_empty() || t.len() > MAX_TUPLE_LEN)
+        {
+            return Err(format!(
+                "tuples must have between 1 and {} cells, found {}",
+                MAX_TUPLE_LEN,
+                t.len()
+            ));
+        }
+        if let Some((x, y)) = tuples
+            .iter()
+            .flat_map(|t| t.iter())
+            .find(|(x, y)| *x >= width || *y >= height)
+        {
+            return Err(format!(
+                "cell ({}, {}) is off a {}x{} board",
+                x, y, width, height
+            ));
+        }
+
+        // on a rectangular board, turning a quarter would change its shape
+        let symmetries = Symmetry::iter()
+            .filter(|s| width == height || !s.swaps_sides())
+            .collect::<Vec<_>>();
+        let patterns = tuples
+            .iter()
+            .enumerate()
+            .flat_map(|(i, t)| {
+                symmetries.iter().map(move |s| {
+                    let cells = t
+                        .iter()
+                        .map(|c| {
+                            let (x, y) = s.inverse().map_cell(*c, width, height);
+                            x + y * width
+                        })
+                        .collect();
+                    (i, cells)
+                })
+            })
+            .collect();
+
+        Ok(NTupleNetwork {
+            width,
+            height,
+            tuples: tuples.iter().map(|t| t.to_vec()).collect(),
+            weights: tuples
+                .iter()
+                .map(|t| vec![0.0; 1 << (4 * t.len())])
+                .collect(),
+            patterns,
+        })
+    }
+
+    /// A network for 4x4 boards over [`DEFAULT_TUPLES`].
+    pub fn new_4x4() -> Self {
+        NTupleNetwork::new(4, 4, DEFAULT_TUPLES).unwrap()
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Writes the network to a binary file, as its tables hold millions of weights. Missing
+    /// parent directories are created.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_bincode(self, path.as_ref())
+    }
+
+    /// Reads a network written by [`NTupleNetwork::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<NTupleNetwork, Box<dyn Error>> {
+        read_bincode(path.as_ref())
+    }
+
+    fn index(cells: u128, pattern: &[usize]) -> usize {
+        pattern.iter().enumerate().fold(0, |acc, (i, c)| {
+            acc | ((((cells >> (4 * c)) & 0xF) as usize) << (4 * i))
+        })
+    }
+
+    /// The learned value of a board, usually an afterstate: the score still to be gained from it.
+    pub fn value(&self, board: &Board) -> f32 {
+        let cells = board.packed();
+        self.patterns
+            .iter()
+            .map(|(t, p)| self.weights[*t][Self::index(cells, p)])
+            .sum()
+    }
+
+    /// Moves the value of a board by `delta`, spread evenly over the weights it reads.
+    pub fn update(&mut self, board: &Board, delta: f32) {
+        let cells = board.packed();
+        let step = delta / self.patterns.len() as f32;
+        for (t, p) in &self.patterns {
+            self.weights[*t][Self::index(cells, p)] += step;
+        }
+    }
+
+    /// How good each available move is: the score it gains plus the value of its afterstate.
+    pub fn move_values(&self, game: &Game) -> EnumMap<Move, Option<f32>> {
+        let mut values = EnumMap::default();
+        for m in game.available_moves() {
+            let (afterstate, score) = game.get_board().shift(m);
+            values[m] = Some(score as f32 + self.value(&afterstate));
+        }
+        values
+    }
+
+    /// The best move, with the score it gains and the afterstate it leads to, or `None` if no
+    /// move is possible.
+    fn best_move(&self, game: &Game) -> Option<(Move, u32, Board)> {
+        game.available_moves()
+            .map(|m| {
+                let (afterstate, score) = game.get_board().shift(m);
+                (m, score, afterstate, score as f32 + self.value(&afterstate))
+            })
+            .max_by(|a, b| a.3.total_cmp(&b.3))
+            .map(|(m, score, afterstate, _)| (m, score, afterstate))
+    }
+}
+
+impl Heuristic for NTupleNetwork {
+    fn score(&self, game: &Game, _: Rng) -> f32 {
+        self.value(game.get_board()).max(0.0)
+    }
+
+    fn name(&self) -> String {
+        "an n-tuple network".to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TdParams {
+    pub seed: u64,
+    /// Games of self-play.
+    pub games: usize,
+    /// How far each update moves the value of a board towards its target.
+    pub learning_rate: f32,
+    /// Games between reports, or 0 to report only once training is done.
+    pub report_every: usize,
+}
+
+impl Default for TdParams {
+    fn default() -> Self {
+        TdParams {
+            seed: fastrand::u64(..),
+            games: 100_000,
+            learning_rate: 0.1,
+            report_every: 1000,
+        }
+    }
+}
+
+/// How the games since the last report went.
+#[derive(Debug, Clone)]
+pub struct TdReport {
+    /// Games played since training started.
+    pub games: usize,
+    pub mean_score: f32,
+    pub max_score: usize,
+    /// Share of games that reached the win tile.
+    pub win_rate: f32,
+}
+
+/// Trains a network by TD(0) on afterstates: the network plays games against itself, greedily,
+/// and after each move nudges the value of the previous afterstate towards the score gained by
+/// the move plus the value of the new afterstate. `on_report` is called every
+/// `params.report_every` games with the network as trained so far, which is a good time to save
+/// it, and training stops early if it returns `false`.
+pub fn train(
+    network: &mut NTupleNetwork,
+    params: &TdParams,
+    mut on_report: impl FnMut(&TdReport, &NTupleNetwork) -> bool,
+) {
+    let mut rng = Rng::new(params.seed);
+    let mut scores = vec![];
+    let mut wins = 0;
+    for played in 1..=params.games {
+        let game = train_game(network, params.learning_rate, rng.u64());
+        scores.push(*game.get_score());
+        wins += game.won() as usize;
+
+        let report_due = params.report_every != 0 && played % params.report_every == 0;
+        if report_due || played == params.games {
+            let report = TdReport {
+                games: played,
+                mean_score: scores.iter().sum::<usize>() as f32 / scores.len() as f32,
+                max_score: scores.iter().copied().max().unwrap_or(0),
+                win_rate: wins as f32 / scores.len() as f32,
+            };
+            scores.clear();
+            wins = 0;
+            if !on_report(&report, network) {
+                return;
+            }
+        }
+    }
+}
+
+/// Plays and learns from one game, returning it once over.
+fn train_game(network: &mut NTupleNetwork, learning_rate: f32, seed: u64) -> Game {
+    let mut game = Game::new_seeded_sized(seed, network.width, network.height);
+    let mut previous: Option<Board> = None;
+    loop {
+        let best = (!game.game_over())
+            .then(|| network.best_move(&game))
+            .flatten();
+        let Some((m, score, afterstate)) = best else {
+            // nothing more can be gained after the last afterstate
+            if let Some(previous) = previous {
+                let error = -network.value(&previous);
+                network.update(&previous, learning_rate * error);
+            }
+            return game;
+        };
+        if let Some(previous) = previous {
+            let error = score as f32 + network.value(&afterstate) - network.value(&previous);
+            network.update(&previous, learning_rate * error);
+        }
+        previous = Some(afterstate);
+        game.make_move(m);
+    }
+}
+
+/// Plays the move an n-tuple network values most.
+pub struct NTupleAgent {
+    game: Game,
+    network: Arc<NTupleNetwork>,
+    last_values: EnumMap<Move, Option<f32>>,
+    last_outcome: Option<MoveOutcome>,
+}
+
+impl NTupleAgent {
+    /// # Panics
+    /// If the network was built for a different board size than the game's.
+    pub fn new(game: Game, network: Arc<NTupleNetwork>) -> Self {
+        assert!(
+            game.width() == network.width && game.height() == network.height,
+            "a network for {}x{} boards can't play on a {}x{} board",
+            network.width,
+            network.height,
+            game.width(),
+            game.height()
+        );
+        NTupleAgent {
+            game,
+            network,
+            last_values: EnumMap::default(),
+            last_outcome: None,
+        }
+    }
+}
+
+impl Agent for NTupleAgent {
+    fn next_move(&self) -> Move {
+        self.network.move_values(&self.game).max_move(&self.game)
+    }
+
+    fn make_move(&mut self) {
+        self.last_values = self.network.move_values(&self.game);
+        let m = self.last_values.max_move(&self.game);
+        self.last_outcome = Some(self.game.make_move(m));
+    }
+
+    fn get_game(&self) -> &Game {
+        &self.game
+    }
+}
+
+impl TuiAgent for NTupleAgent {
+    fn messages(&self) -> Vec<Spans<'_>> {
+        let highest_move = self
+            .last_values
+            .iter()
+            .filter_map(|(m, v)| v.map(|v| (m, v)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(m, _)| m);
+
+        let mut value_spans = Move::iter()
+            .map(|m| {
+                let text = match self.last_values[m] {
+                    Some(v) => format!("{}: {:.0}", m, v),
+                    None => format!("{}: -", m),
+                };
+                if Some(m) == highest_move {
+                    Spans::from(Span::styled(
+                        text,
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .bg(Color::Green),
+                    ))
+                } else {
+                    Spans::from(text)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut msgs = vec![
+            Spans::from(Span::styled(
+                "N-Tuple Network",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Spans::from(format!(
+                "Playing the move with the highest score plus learned afterstate value, over {} tuples.",
+                self.network.tuples.len()
+            )),
+            Spans::from(""),
+        ];
+        msgs.append(&mut value_spans);
+        msgs
+    }
+
+    fn last_outcome(&self) -> Option<&MoveOutcome> {
+        self.last_outcome.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symmetric_value() {
+        let mut network = NTupleNetwork::new_4x4();
+        let board = "2 4 . ./. 8 . ./. . . ./. . . 2".parse::<Board>().unwrap();
+        network.update(&board, 10.0);
+        // patterns reading the same cells share a weight, so the value can move by more
+        let value = network.value(&board);
+        assert!(value >= 10.0 - 1e-3);
+        for s in Symmetry::iter() {
+            assert!((network.value(&board.transform(s)) - value).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_train() {
+        let mut network = NTupleNetwork::new_4x4();
+        let params = TdParams {
+            seed: 3,
+            games: 20,
+            report_every: 10,
+            ..TdParams::default()
+        };
+        let mut reports = vec![];
+        train(&mut network, &params, |r, _| {
+            reports.push(r.clone());
+            true
+        });
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[1].games, 20);
+        assert_ne!(network, NTupleNetwork::new_4x4());
+
+        let path = std::env::temp_dir().join(format!("ai-2048-test-td-{}.bin", std::process::id()));
+        network.save(&path).unwrap();
+        assert_eq!(NTupleNetwork::load(&path).unwrap(), network);
+        std::fs::remove_file(&path).unwrap();
+
+        // with no report interval, training only reports at the end
+        let params = TdParams {
+            seed: 3,
+            games: 3,
+            report_every: 0,
+            ..TdParams::default()
+        };
+        let mut reports = vec![];
+        train(&mut network, &params, |r, _| {
+            reports.push(r.clone());
+            true
+        });
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].games, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "game that is over")]
+    fn test_no_move_when_over() {
+        let game = "2 4 2 4/4 2 4 2/2 4 2 4/4 2 4 2".parse::<Game>().unwrap();
+        NTupleAgent::new(game, Arc::new(NTupleNetwork::new_4x4())).next_move();
+    }
+
+    #[test]
+    fn test_rectangular_network() {
+        let network = NTupleNetwork::new(3, 2, &[&[(0, 0), (1, 0), (2, 0)]]).unwrap();
+        assert_eq!(network.patterns.len(), 4);
+        assert!(NTupleNetwork::new(3, 2, &[&[(0, 2)]]).is_err());
+    }
+}
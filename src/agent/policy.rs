@@ -0,0 +1,189 @@
+//! Policies pick moves for positions they don't own, unlike [`Agent`]s, which play their own
+//! game. A policy holds only its settings, so it can advise on any position, be reused across
+//! games, or be driven by a game loop of its own; [`PolicyAgent`] plays one in a game of its own,
+//! for the TUI.
+
+use strum::IntoEnumIterator;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+use crate::game::{outcome::MoveOutcome, rng::Rng, Game, Move};
+
+use super::{Agent, MoveScores, TuiAgent};
+
+/// The move a policy picked, and how it scored every move if it scores them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub direction: Move,
+    /// Higher is better. Moves that aren't possible score 0.
+    pub scores: Option<MoveScores>,
+}
+
+pub trait Policy {
+    /// Picks a move for a game that isn't over. Policies that play at random draw from `rng`, so
+    /// a loop that passes the same stream along is reproducible.
+    fn decide(&self, game: &Game, rng: &mut Rng) -> Decision;
+    fn name(&self) -> &'static str;
+
+    /// What the TUI shows about the last decision, if one was made: by default the policy's
+    /// name, the move played, and the scores of every move.
+    fn messages(&self, decision: Option<&Decision>) -> Vec<Spans<'static>> {
+        let mut msgs = vec![Spans::from(Span::styled(
+            self.name(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ))];
+        let Some(decision) = decision else {
+            return msgs;
+        };
+        msgs.push(Spans::from(format!("Played {}.", decision.direction)));
+        if let Some(scores) = decision.scores {
+            msgs.push(Spans::from(""));
+            msgs.extend(score_spans(decision.direction, |m| scores[m]));
+        }
+        msgs
+    }
+}
+
+/// One line per move with its score, the move played highlighted.
+pub(crate) fn score_spans(
+    played: Move,
+    score: impl Fn(Move) -> usize,
+) -> impl Iterator<Item = Spans<'static>> {
+    Move::iter().map(move |m| {
+        let text = format!("{}: {}", m, score(m));
+        if m == played {
+            Spans::from(Span::styled(
+                text,
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .bg(Color::Green),
+            ))
+        } else {
+            Spans::from(text)
+        }
+    })
+}
+
+/// Plays a policy in a game of its own.
+pub struct PolicyAgent<P: Policy> {
+    game: Game,
+    policy: P,
+    rng: Rng,
+    last_decision: Option<Decision>,
+    last_outcome: Option<MoveOutcome>,
+}
+
+impl<P: Policy + Default> PolicyAgent<P> {
+    /// Plays the policy's default settings.
+    pub fn new(game: Game) -> Self {
+        PolicyAgent::with_policy(game, P::default(), Rng::from_entropy())
+    }
+
+    pub fn new_seeded(seed: u64, game: Game) -> Self {
+        PolicyAgent::with_policy(game, P::default(), Rng::new(seed))
+    }
+
+    pub fn with_rng(game: Game, rng: Rng) -> Self {
+        PolicyAgent::with_policy(game, P::default(), rng)
+    }
+}
+
+impl<P: Policy> PolicyAgent<P> {
+    pub fn with_policy(game: Game, policy: P, rng: Rng) -> Self {
+        PolicyAgent {
+            game,
+            policy,
+            rng,
+            last_decision: None,
+            last_outcome: None,
+        }
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+}
+
+impl<P: Policy> Agent for PolicyAgent<P> {
+    // peeks at the agent's stream, so this is the move `make_move` will play
+    fn next_move(&self) -> Move {
+        let mut rng = self.rng;
+        self.policy.decide(&self.game, &mut rng).direction
+    }
+
+    fn make_move(&mut self) {
+        let decision = self.policy.decide(&self.game, &mut self.rng);
+        self.last_decision = Some(decision);
+        self.last_outcome = Some(self.game.make_move(decision.direction));
+    }
+
+    fn get_game(&self) -> &Game {
+        &self.game
+    }
+}
+
+impl<P: Policy> TuiAgent for PolicyAgent<P> {
+    fn messages(&self) -> Vec<Spans<'_>> {
+        self.policy.messages(self.last_decision.as_ref())
+    }
+
+    fn last_outcome(&self) -> Option<&MoveOutcome> {
+        self.last_outcome.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{
+        expectimax::{Expectimax, ExpectimaxParams, ExpectimaxPolicy},
+        random::{RandomPolicy, RandomTreeMetric, RandomTreePolicy},
+    };
+
+    #[test]
+    fn test_adapter_matches_policy() {
+        let mut game = Game::new_seeded(7);
+        let mut rng = Rng::new(8);
+        let mut adapter = PolicyAgent::with_policy(game, RandomPolicy, Rng::new(8));
+        while !game.game_over() {
+            let decision = RandomPolicy.decide(&game, &mut rng);
+            assert_eq!(adapter.next_move(), decision.direction);
+            game.make_move(decision.direction);
+            adapter.make_move();
+        }
+        assert_eq!(adapter.get_game().get_board(), game.get_board());
+        assert!(adapter.get_game().game_over());
+    }
+
+    #[test]
+    fn test_advise_any_position() {
+        let params = ExpectimaxParams {
+            seed: 1,
+            depth: 1,
+            ..ExpectimaxParams::default()
+        };
+        let policy = ExpectimaxPolicy::new(params.clone());
+        let tree = RandomTreePolicy {
+            sim_count: 20,
+            metric: RandomTreeMetric::AvgScore,
+            parallel: false,
+        };
+        let mut rng = Rng::new(0);
+        for seed in 0..3 {
+            let game = Game::new_seeded(seed);
+            let decision = policy.decide(&game, &mut rng);
+            let agent = Expectimax::new_with(game, params.clone());
+            assert_eq!(decision.direction, agent.next_move());
+            assert!(game.can_move(decision.direction));
+            assert!(decision.scores.is_some());
+
+            // the tree searches with a generator split from the stream it is handed
+            let mut peek = rng;
+            let decision = tree.decide(&game, &mut rng);
+            assert!(game.can_move(decision.direction));
+            assert_eq!(decision.scores, Some(tree.score_game(&game, peek.split())));
+        }
+    }
+}
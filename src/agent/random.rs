@@ -1,132 +1,167 @@
 use crate::agent::Agent;
+use crate::game::rng::Rng;
 use crate::game::{Game, Move};
 
 use rayon::prelude::*;
 use strum::IntoEnumIterator;
-use tui::style::{Color, Modifier, Style};
+use tui::style::{Modifier, Style};
 use tui::text::{Span, Spans};
 
-use super::{MaxMove, MoveScores, TuiAgent};
+use super::{
+    policy::{score_spans, Decision, Policy, PolicyAgent},
+    MaxMove, MoveScores,
+};
 
-// Basic random agent, randomly selects an action and takes the move.
-pub struct RandomAgent {
-    game: Game,
-}
-
-impl RandomAgent {
-    pub fn new(game: Game) -> Self {
-        RandomAgent { game }
-    }
+/// Basic random agent, randomly selects an action and takes the move.
+pub type RandomAgent = PolicyAgent<RandomPolicy>;
 
-    pub fn new_seeded(seed: u64, game: Game) -> Self {
-        fastrand::seed(seed);
-        RandomAgent { game }
-    }
+fn random_move(game: &Game, rng: &mut Rng) -> Move {
+    let num_avail = game.available_moves().count();
+    game.available_moves().nth(rng.usize(0..num_avail)).unwrap()
 }
 
-impl Agent for RandomAgent {
-    fn next_move(&self) -> Move {
-        let avail = self.game.available_moves();
-        return avail[fastrand::usize(0..avail.len())];
-    }
+/// Picks random moves for any position.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RandomPolicy;
 
-    fn make_move(&mut self) {
-        self.game.make_move(self.next_move());
+impl Policy for RandomPolicy {
+    fn decide(&self, game: &Game, rng: &mut Rng) -> Decision {
+        Decision {
+            direction: random_move(game, rng),
+            scores: None,
+        }
     }
 
-    fn get_game(&self) -> &Game {
-        &self.game
+    fn name(&self) -> &'static str {
+        "Random"
     }
-}
 
-impl TuiAgent for RandomAgent {
-    fn messages(&self) -> Vec<Spans> {
+    fn messages(&self, _: Option<&Decision>) -> Vec<Spans<'static>> {
         vec![Spans::from("Performing random actions.")]
     }
 }
 
-// Use a RandomAgent to simulate a full game from a starting point
-pub fn simulate_random_game(game: Game) -> Game {
-    let mut agent = RandomAgent::new(game);
+// Use a RandomAgent to simulate a full game from a starting point. Both the agent's moves and the
+// game's tile spawns are drawn from streams derived from `seed`.
+pub fn simulate_random_game(mut game: Game, seed: u64) -> Game {
+    let mut rng = Rng::new(seed);
+    game.set_rng(rng.split());
+    let mut agent = RandomAgent::with_rng(game, rng);
     while !agent.get_game().game_over() {
         agent.make_move();
     }
-    agent.game
+    *agent.get_game()
+}
+
+/// Random tree search, simulate many games per move and then select the move based on the
+/// highest average score.
+pub type RandomTree = PolicyAgent<RandomTreePolicy>;
+
+/// Random tree search for any position: how many games to simulate per move, and what to compare
+/// them by.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomTreePolicy {
+    pub sim_count: usize,
+    pub metric: RandomTreeMetric,
+    pub parallel: bool,
 }
 
-// Random tree search, simulate many games per move and then select the move based on the highest
-// average score.
-pub struct RandomTree {
-    game: Game,
-    sim_count: usize,
-    metric: RandomTreeMetric,
-    last_scores: MoveScores,
-    parallel: bool,
+impl Default for RandomTreePolicy {
+    fn default() -> Self {
+        RandomTreePolicy {
+            sim_count: 1000,
+            metric: RandomTreeMetric::AvgScore,
+            parallel: true,
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RandomTreeMetric {
     AvgScore,
     AvgMoves,
 }
 
 impl RandomTree {
-    pub fn new(game: Game) -> Self {
-        RandomTree {
-            game,
-            sim_count: 1000,
-            metric: RandomTreeMetric::AvgScore,
-            last_scores: MoveScores::default(),
-            parallel: true,
-        }
+    /// # Panics
+    /// If `sim_count` is 0.
+    pub fn new_with(
+        game: Game,
+        sim_count: usize,
+        metric: RandomTreeMetric,
+        parallel: bool,
+    ) -> Self {
+        RandomTree::new_seeded_with(Rng::from_entropy().u64(), game, sim_count, metric, parallel)
     }
 
-    pub fn new_with(
+    /// # Panics
+    /// If `sim_count` is 0.
+    pub fn new_seeded_with(
+        seed: u64,
         game: Game,
         sim_count: usize,
         metric: RandomTreeMetric,
         parallel: bool,
     ) -> Self {
-        let mut ag = RandomTree::new(game);
-        ag.sim_count = sim_count;
-        ag.metric = metric;
-        ag.parallel = parallel;
-        ag
+        let policy = RandomTreePolicy::new(sim_count, metric, parallel);
+        PolicyAgent::with_policy(game, policy, Rng::new(seed))
     }
+}
+
+impl RandomTreePolicy {
+    /// # Panics
+    /// If `sim_count` is 0, since moves are compared by their average over the simulations.
+    pub fn new(sim_count: usize, metric: RandomTreeMetric, parallel: bool) -> Self {
+        assert!(
+            sim_count > 0,
+            "random tree search needs at least one simulation"
+        );
+        RandomTreePolicy {
+            sim_count,
+            metric,
+            parallel,
+        }
+    }
+
+    fn simulate(&self, game: &Game, game_move: Move, seed: u64) -> usize {
+        let mut rng = Rng::new(seed);
+        let mut sim_game = *game;
+        sim_game.set_rng(rng.split());
+        sim_game.make_move(game_move);
+        let game = simulate_random_game(sim_game, rng.u64());
+        match self.metric {
+            RandomTreeMetric::AvgMoves => *game.get_num_moves(),
+            RandomTreeMetric::AvgScore => *game.get_score(),
+        }
+    }
+
+    /// Scores every move of any game, drawing the simulations' seeds from `rng`.
+    ///
+    /// # Panics
+    /// If `sim_count` is 0.
+    pub fn score_game(&self, game: &Game, mut rng: Rng) -> MoveScores {
+        assert!(
+            self.sim_count > 0,
+            "random tree search needs at least one simulation"
+        );
+        // every move is simulated with the same seeds, so they are compared on equal footing
+        let seeds = (0..self.sim_count).map(|_| rng.u64()).collect::<Vec<_>>();
 
-    pub fn score_moves(&self) -> MoveScores {
         let mut scores = MoveScores::default();
         for game_move in Move::iter() {
-            let mut sim_game = self.game.clone();
-            let test = sim_game.make_move(game_move);
-            if !test {
+            if !game.can_move(game_move) {
                 continue;
             }
 
             let score = if self.parallel {
-                vec![0; self.sim_count]
+                seeds
                     .par_iter()
-                    .map(|_| {
-                        let mut sim_game = self.game.clone();
-                        sim_game.make_move(game_move);
-                        let game = simulate_random_game(sim_game);
-                        match self.metric {
-                            RandomTreeMetric::AvgMoves => game.get_num_moves().clone(),
-                            RandomTreeMetric::AvgScore => game.get_score().clone(),
-                        }
-                    })
+                    .map(|seed| self.simulate(game, game_move, *seed))
                     .sum::<usize>()
             } else {
-                vec![0; self.sim_count]
+                seeds
                     .iter()
-                    .map(|_| {
-                        let mut sim_game = self.game.clone();
-                        sim_game.make_move(game_move);
-                        let game = simulate_random_game(sim_game);
-                        match self.metric {
-                            RandomTreeMetric::AvgMoves => game.get_num_moves().clone(),
-                            RandomTreeMetric::AvgScore => game.get_score().clone(),
-                        }
-                    })
+                    .map(|seed| self.simulate(game, game_move, *seed))
                     .sum::<usize>()
             };
 
@@ -137,50 +172,23 @@ impl RandomTree {
     }
 }
 
-impl Agent for RandomTree {
-    fn next_move(&self) -> Move {
-        let scores = self.score_moves();
-        scores.max_move()
-    }
-
-    fn make_move(&mut self) {
-        let scores = self.score_moves();
-        self.last_scores = scores;
-        self.game.make_move(scores.max_move());
+impl Policy for RandomTreePolicy {
+    fn decide(&self, game: &Game, rng: &mut Rng) -> Decision {
+        let scores = self.score_game(game, rng.split());
+        Decision {
+            direction: scores.max_move(game),
+            scores: Some(scores),
+        }
     }
 
-    fn get_game(&self) -> &Game {
-        &self.game
+    fn name(&self) -> &'static str {
+        "Random Tree Search"
     }
-}
-
-impl TuiAgent for RandomTree {
-    fn messages(&self) -> Vec<Spans> {
-        let highest_move = self
-            .last_scores
-            .iter()
-            .max_by_key(|(_, score)| *score)
-            .unwrap()
-            .0;
-
-        let mut score_spans = Move::iter()
-            .map(|m| {
-                if m == highest_move {
-                    Spans::from(Span::styled(
-                        format!("{}: {}", m, self.last_scores[m] / self.sim_count),
-                        Style::default()
-                            .add_modifier(Modifier::BOLD)
-                            .bg(Color::Green),
-                    ))
-                } else {
-                    Spans::from(format!("{}: {}", m, self.last_scores[m] / self.sim_count))
-                }
-            })
-            .collect::<Vec<_>>();
 
+    fn messages(&self, decision: Option<&Decision>) -> Vec<Spans<'static>> {
         let mut msgs = vec![
             Spans::from(Span::styled(
-                "Random Tree Search",
+                self.name(),
                 Style::default().add_modifier(Modifier::BOLD),
             )),
             Spans::from(format!(
@@ -193,7 +201,24 @@ impl TuiAgent for RandomTree {
             )),
             Spans::from(""),
         ];
-        msgs.append(&mut score_spans);
+        if let Some(Decision {
+            direction,
+            scores: Some(scores),
+        }) = decision
+        {
+            msgs.extend(score_spans(*direction, |m| scores[m] / self.sim_count));
+        }
         msgs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "at least one simulation")]
+    fn test_tree_needs_simulations() {
+        RandomTree::new_with(Game::new_seeded(0), 0, RandomTreeMetric::AvgScore, false);
+    }
+}
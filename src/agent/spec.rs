@@ -0,0 +1,585 @@
+//! Agents described by a line of text, so that they can be picked from the command line.
+//!
+//! A spec is an agent's name, optionally followed by a colon and comma separated settings, such
+//! as `expectimax:depth=3,heuristic=weighted` or `tree:sims=200,metric=moves`. Settings left out
+//! keep their defaults.
+//!
+//! The trained agents, `ntuple` and `learned`, load what they play with when the spec is parsed,
+//! from the file given by `path` or else from where the TUI saves it. So do the `weighted`
+//! Expectimax heuristic, from the file given by `weights`, and the `weighted` MCTS rollout, both
+//! falling back on the default weights if none were tuned.
+
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use strum::IntoEnumIterator;
+
+use crate::{game::Game, storage};
+
+use super::{
+    corner::{Corner, CornerAgent},
+    expectimax::{Expectimax, ExpectimaxParams},
+    heuristic::{GameOverHeuristic, Heuristic, WeightedSum},
+    learned::LinearValue,
+    mcts::{Budget, Mcts, MctsParams, Rollout},
+    ntuple::{NTupleAgent, NTupleNetwork},
+    random::{RandomAgent, RandomTree, RandomTreeMetric},
+    TuiAgent,
+};
+
+/// How an Expectimax spec scores the end of its lines of play.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeuristicSpec {
+    /// Random games played to the end, as many as `sims`.
+    Rollout { sims: usize },
+    /// A weighted sum of board features, read from `path` if one was given.
+    Weighted {
+        path: Option<PathBuf>,
+        weights: Arc<WeightedSum>,
+    },
+}
+
+impl HeuristicSpec {
+    /// The weights tuned from the TUI or with `train weights`.
+    pub fn weighted() -> HeuristicSpec {
+        HeuristicSpec::Weighted {
+            path: None,
+            weights: tuned_weights(),
+        }
+    }
+}
+
+/// How an MCTS spec scores the positions it adds to its tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RolloutSpec {
+    Random,
+    Greedy,
+    /// The weights tuned from the TUI or with `train weights`, without playing on.
+    Weighted(Arc<WeightedSum>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentSpec {
+    Random,
+    RandomTree {
+        sims: usize,
+        metric: RandomTreeMetric,
+    },
+    Expectimax {
+        depth: usize,
+        min_probability: f32,
+        heuristic: HeuristicSpec,
+        /// Milliseconds to spend on each move, searching deeper as time allows up to `depth`.
+        time_ms: Option<u64>,
+    },
+    Mcts {
+        iterations: usize,
+        exploration: f32,
+        rollout: RolloutSpec,
+        /// Milliseconds to spend on each move, in place of running `iterations`.
+        time_ms: Option<u64>,
+    },
+    Corner {
+        corner: Corner,
+    },
+    /// An n-tuple network, as trained from the TUI or with `train ntuple`.
+    NTuple {
+        path: PathBuf,
+        network: Arc<NTupleNetwork>,
+    },
+    /// Expectimax over a learned value function, as trained from the TUI or with `train value`.
+    Learned {
+        path: PathBuf,
+        value: Arc<LinearValue>,
+        depth: usize,
+    },
+}
+
+impl AgentSpec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentSpec::Random => "random",
+            AgentSpec::RandomTree { .. } => "tree",
+            AgentSpec::Expectimax { .. } => "expectimax",
+            AgentSpec::Mcts { .. } => "mcts",
+            AgentSpec::Corner { .. } => "corner",
+            AgentSpec::NTuple { .. } => "ntuple",
+            AgentSpec::Learned { .. } => "learned",
+        }
+    }
+
+    /// The spec of an agent with default settings, by name. Trained agents are loaded from
+    /// `path`, or from where the TUI saves them if it is `None`; other agents take no path.
+    pub fn default_for(name: &str, path: Option<PathBuf>) -> Result<AgentSpec, String> {
+        if let Some(path) = path {
+            return match name {
+                "ntuple" => Ok(AgentSpec::NTuple {
+                    network: Arc::new(load(&path, |p| NTupleNetwork::load(p))?),
+                    path,
+                }),
+                "learned" => Ok(AgentSpec::Learned {
+                    value: Arc::new(load(&path, |p| LinearValue::load(p))?),
+                    path,
+                    // as deep as the TUI plays it
+                    depth: 3,
+                }),
+                _ => Err(format!("{} has no setting 'path'", name)),
+            };
+        }
+
+        let expectimax = ExpectimaxParams::default();
+        let mcts = MctsParams::default();
+        Ok(match name {
+            "random" => AgentSpec::Random,
+            "tree" => AgentSpec::RandomTree {
+                sims: 1000,
+                metric: RandomTreeMetric::AvgScore,
+            },
+            "expectimax" => AgentSpec::Expectimax {
+                depth: expectimax.depth,
+                min_probability: expectimax.min_probability,
+                heuristic: HeuristicSpec::Rollout { sims: 10 },
+                time_ms: None,
+            },
+            "mcts" => AgentSpec::Mcts {
+                iterations: 2000,
+                exploration: mcts.exploration,
+                rollout: RolloutSpec::Random,
+                time_ms: None,
+            },
+            "corner" => AgentSpec::Corner {
+                corner: Corner::default(),
+            },
+            "ntuple" => {
+                let path = storage::ntuple_path().map_err(|e| e.to_string())?;
+                return AgentSpec::default_for(name, Some(path));
+            }
+            "learned" => {
+                let path = storage::value_path().map_err(|e| e.to_string())?;
+                return AgentSpec::default_for(name, Some(path));
+            }
+            _ => {
+                return Err(format!(
+                    "unknown agent '{}', expected one of random, tree, expectimax, mcts, corner, ntuple, learned",
+                    name
+                ))
+            }
+        })
+    }
+
+    /// Builds the agent to play `game`, drawing any randomness of its own from `seed`.
+    pub fn build(&self, game: Game, seed: u64) -> Box<dyn TuiAgent + Send + Sync> {
+        match *self {
+            AgentSpec::Random => Box::new(RandomAgent::new_seeded(seed, game)),
+            AgentSpec::RandomTree { sims, metric } => {
+                Box::new(RandomTree::new_seeded_with(seed, game, sims, metric, true))
+            }
+            AgentSpec::Expectimax {
+                depth,
+                min_probability,
+                ref heuristic,
+                time_ms,
+            } => {
+                let heuristic: Arc<dyn Heuristic> = match heuristic {
+                    HeuristicSpec::Rollout { sims } => Arc::new(GameOverHeuristic { sims: *sims }),
+                    HeuristicSpec::Weighted { weights, .. } => weights.clone(),
+                };
+                let params = ExpectimaxParams {
+                    seed,
+                    depth,
+                    min_probability,
+                    heuristic,
+                    time_budget: time_ms.map(Duration::from_millis),
+                    ..ExpectimaxParams::default()
+                };
+                Box::new(Expectimax::new_with(game, params))
+            }
+            AgentSpec::Mcts {
+                iterations,
+                exploration,
+                ref rollout,
+                time_ms,
+            } => {
+                let params = MctsParams {
+                    seed,
+                    exploration,
+                    rollout: match rollout {
+                        RolloutSpec::Random => Rollout::Random,
+                        RolloutSpec::Greedy => Rollout::Greedy,
+                        RolloutSpec::Weighted(weights) => Rollout::Heuristic(weights.clone()),
+                    },
+                    budget: match time_ms {
+                        Some(ms) => Budget::Time(Duration::from_millis(ms)),
+                        None => Budget::Iterations(iterations),
+                    },
+                };
+                Box::new(Mcts::new_with(game, params))
+            }
+            AgentSpec::Corner { corner } => Box::new(CornerAgent::new_with(game, corner)),
+            AgentSpec::NTuple { ref network, .. } => {
+                Box::new(NTupleAgent::new(game, network.clone()))
+            }
+            AgentSpec::Learned {
+                ref value, depth, ..
+            } => {
+                let params = ExpectimaxParams {
+                    seed,
+                    depth,
+                    heuristic: value.clone(),
+                    ..ExpectimaxParams::default()
+                };
+                Box::new(Expectimax::new_with(game, params))
+            }
+        }
+    }
+
+    /// Changes one setting, given as text.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value '{}' for {}", value, key))
+        }
+
+        match (self, key) {
+            (AgentSpec::RandomTree { sims, .. }, "sims") => {
+                *sims = parse(key, value)?;
+                if *sims == 0 {
+                    return Err("sims must be at least 1".to_string());
+                }
+            }
+            (AgentSpec::RandomTree { metric, .. }, "metric") => {
+                *metric = match value {
+                    "score" => RandomTreeMetric::AvgScore,
+                    "moves" => RandomTreeMetric::AvgMoves,
+                    _ => return Err(format!("metric must be score or moves, found '{}'", value)),
+                }
+            }
+            (AgentSpec::Expectimax { depth, .. } | AgentSpec::Learned { depth, .. }, "depth") => {
+                *depth = parse(key, value)?;
+                if *depth == 0 {
+                    return Err("depth must be at least 1".to_string());
+                }
+            }
+            (
+                AgentSpec::Expectimax {
+                    min_probability, ..
+                },
+                "min_probability",
+            ) => {
+                *min_probability = parse(key, value)?;
+                if !(0.0..1.0).contains(min_probability) {
+                    return Err("min_probability must be at least 0 and less than 1".to_string());
+                }
+            }
+            (AgentSpec::Expectimax { heuristic, .. }, "heuristic") => {
+                *heuristic = match value {
+                    "rollout" => HeuristicSpec::Rollout { sims: 10 },
+                    "weighted" => HeuristicSpec::weighted(),
+                    _ => {
+                        return Err(format!(
+                            "heuristic must be rollout or weighted, found '{}'",
+                            value
+                        ))
+                    }
+                }
+            }
+            (
+                AgentSpec::Expectimax {
+                    heuristic: HeuristicSpec::Rollout { sims },
+                    ..
+                },
+                "sims",
+            ) => {
+                *sims = parse(key, value)?;
+                if *sims == 0 {
+                    return Err("sims must be at least 1".to_string());
+                }
+            }
+            (
+                AgentSpec::Expectimax {
+                    heuristic: HeuristicSpec::Weighted { path, weights },
+                    ..
+                },
+                "weights",
+            ) => {
+                let file = PathBuf::from(value);
+                *weights = Arc::new(load(&file, |p| WeightedSum::load(p))?);
+                *path = Some(file);
+            }
+            (AgentSpec::Expectimax { time_ms, .. }, "time_ms") => {
+                let ms = parse(key, value)?;
+                if ms == 0 {
+                    return Err("time_ms must be at least 1".to_string());
+                }
+                *time_ms = Some(ms);
+            }
+            (AgentSpec::Mcts { iterations, .. }, "iterations") => {
+                *iterations = parse(key, value)?;
+                if *iterations == 0 {
+                    return Err("iterations must be at least 1".to_string());
+                }
+            }
+            (AgentSpec::Mcts { exploration, .. }, "exploration") => {
+                *exploration = parse(key, value)?
+            }
+            (AgentSpec::Mcts { rollout, .. }, "rollout") => {
+                *rollout = match value {
+                    "random" => RolloutSpec::Random,
+                    "greedy" => RolloutSpec::Greedy,
+                    "weighted" => RolloutSpec::Weighted(tuned_weights()),
+                    _ => {
+                        return Err(format!(
+                            "rollout must be random, greedy or weighted, found '{}'",
+                            value
+                        ))
+                    }
+                }
+            }
+            (AgentSpec::Mcts { time_ms, .. }, "time_ms") => {
+                let ms = parse(key, value)?;
+                if ms == 0 {
+                    return Err("time_ms must be at least 1".to_string());
+                }
+                *time_ms = Some(ms);
+            }
+            (AgentSpec::Corner { corner }, "corner") => {
+                *corner = Corner::iter()
+                    .find(|c| corner_name(*c) == value)
+                    .ok_or_else(|| format!("unknown corner '{}'", value))?
+            }
+            (spec, _) => return Err(format!("{} has no setting '{}'", spec.name(), key)),
+        }
+        Ok(())
+    }
+}
+
+/// The weights tuned from the TUI or with `train weights`, or the default ones if there are none.
+fn tuned_weights() -> Arc<WeightedSum> {
+    Arc::new(
+        storage::weights_path()
+            .and_then(WeightedSum::load)
+            .unwrap_or_default(),
+    )
+}
+
+/// Loads a trained model, naming the file if it can't.
+fn load<T>(
+    path: &Path,
+    load: impl FnOnce(&Path) -> Result<T, Box<dyn Error>>,
+) -> Result<T, String> {
+    load(path).map_err(|e| format!("could not load {}: {}", path.display(), e))
+}
+
+fn corner_name(corner: Corner) -> String {
+    corner.to_string().replace(' ', "-")
+}
+
+impl FromStr for AgentSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, settings) = s.split_once(':').unwrap_or((s, ""));
+        let settings = settings
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|setting| {
+                setting
+                    .split_once('=')
+                    .map(|(key, value)| (key.trim(), value.trim()))
+                    .ok_or_else(|| format!("expected key=value, found '{}'", setting))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // the path is needed to load trained agents, so it is taken before any other setting
+        let path = settings
+            .iter()
+            .find(|(key, _)| *key == "path")
+            .map(|(_, value)| PathBuf::from(value));
+        let mut spec = AgentSpec::default_for(name.trim(), path)?;
+        // settings are applied in order, so `sims` may follow the `heuristic` it belongs to
+        for (key, value) in settings.into_iter().filter(|(key, _)| *key != "path") {
+            spec.set(key, value)?;
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for AgentSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        match self {
+            AgentSpec::Random => Ok(()),
+            AgentSpec::RandomTree { sims, metric } => {
+                let metric = match metric {
+                    RandomTreeMetric::AvgScore => "score",
+                    RandomTreeMetric::AvgMoves => "moves",
+                };
+                write!(f, ":sims={},metric={}", sims, metric)
+            }
+            AgentSpec::Expectimax {
+                depth,
+                min_probability,
+                heuristic,
+                time_ms,
+            } => {
+                write!(f, ":depth={},min_probability={}", depth, min_probability)?;
+                match heuristic {
+                    HeuristicSpec::Rollout { sims } => {
+                        write!(f, ",heuristic=rollout,sims={}", sims)?
+                    }
+                    HeuristicSpec::Weighted { path: None, .. } => write!(f, ",heuristic=weighted")?,
+                    HeuristicSpec::Weighted {
+                        path: Some(path), ..
+                    } => write!(f, ",heuristic=weighted,weights={}", path.display())?,
+                }
+                match time_ms {
+                    Some(ms) => write!(f, ",time_ms={}", ms),
+                    None => Ok(()),
+                }
+            }
+            AgentSpec::Mcts {
+                iterations,
+                exploration,
+                rollout,
+                time_ms,
+            } => {
+                let rollout = match rollout {
+                    RolloutSpec::Random => "random",
+                    RolloutSpec::Greedy => "greedy",
+                    RolloutSpec::Weighted(_) => "weighted",
+                };
+                write!(
+                    f,
+                    ":iterations={},exploration={},rollout={}",
+                    iterations, exploration, rollout
+                )?;
+                match time_ms {
+                    Some(ms) => write!(f, ",time_ms={}", ms),
+                    None => Ok(()),
+                }
+            }
+            AgentSpec::Corner { corner } => write!(f, ":corner={}", corner_name(*corner)),
+            AgentSpec::NTuple { path, .. } => write!(f, ":path={}", path.display()),
+            AgentSpec::Learned { path, depth, .. } => {
+                write!(f, ":path={},depth={}", path.display(), depth)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("random".parse::<AgentSpec>(), Ok(AgentSpec::Random));
+        let spec = "expectimax:depth=3, heuristic=rollout, sims=4"
+            .parse::<AgentSpec>()
+            .unwrap();
+        assert_eq!(
+            spec,
+            AgentSpec::Expectimax {
+                depth: 3,
+                min_probability: 1e-3,
+                heuristic: HeuristicSpec::Rollout { sims: 4 },
+                time_ms: None,
+            }
+        );
+
+        for s in [
+            "random",
+            "tree:sims=20,metric=moves",
+            "expectimax:depth=1,min_probability=0.01,heuristic=weighted,time_ms=50",
+            "mcts:iterations=100,exploration=0.5,rollout=greedy",
+            "mcts:iterations=100,exploration=1,rollout=weighted,time_ms=20",
+            "corner:corner=top-right",
+        ] {
+            let spec = s.parse::<AgentSpec>().unwrap();
+            assert_eq!(spec.to_string(), s);
+        }
+
+        assert!("minimax".parse::<AgentSpec>().is_err());
+        assert!("random:depth=2".parse::<AgentSpec>().is_err());
+        assert!("expectimax:depth=0".parse::<AgentSpec>().is_err());
+        assert!("tree:sims=0".parse::<AgentSpec>().is_err());
+        assert!("mcts:iterations=0".parse::<AgentSpec>().is_err());
+        assert!("mcts:time_ms=0".parse::<AgentSpec>().is_err());
+        assert!("expectimax:time_ms=0".parse::<AgentSpec>().is_err());
+        assert!("expectimax:min_probability=1".parse::<AgentSpec>().is_err());
+        assert!("expectimax:min_probability=-0.1"
+            .parse::<AgentSpec>()
+            .is_err());
+        assert!("expectimax:min_probability=NaN"
+            .parse::<AgentSpec>()
+            .is_err());
+        assert!("expectimax:min_probability=0".parse::<AgentSpec>().is_ok());
+        assert!("mcts:rollout=heuristic".parse::<AgentSpec>().is_err());
+        assert!("corner:path=corner.ron".parse::<AgentSpec>().is_err());
+        assert!("expectimax:depth".parse::<AgentSpec>().is_err());
+        assert!("expectimax:heuristic=weighted,sims=3"
+            .parse::<AgentSpec>()
+            .is_err());
+        assert!("expectimax:heuristic=rollout,sims=0"
+            .parse::<AgentSpec>()
+            .is_err());
+        assert!("expectimax:heuristic=rollout,weights=weights.ron"
+            .parse::<AgentSpec>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_trained_agents() {
+        use linfa::traits::Fit;
+
+        use crate::agent::learned::{generate_dataset, RidgeRegression};
+
+        let dir = std::env::temp_dir();
+        let ntuple = dir.join(format!("ai-2048-test-ntuple-{}.bin", std::process::id()));
+        let value = dir.join(format!("ai-2048-test-value-{}.ron", std::process::id()));
+        let weights = dir.join(format!("ai-2048-test-weights-{}.ron", std::process::id()));
+        NTupleNetwork::new_4x4().save(&ntuple).unwrap();
+        WeightedSum::default().save(&weights).unwrap();
+        let dataset = generate_dataset(2, 1, |game, seed| RandomAgent::new_seeded(seed, game));
+        RidgeRegression::default()
+            .fit(&dataset)
+            .unwrap()
+            .save(&value)
+            .unwrap();
+
+        for s in [
+            format!("ntuple:path={}", ntuple.display()),
+            format!("learned:path={},depth=1", value.display()),
+            format!(
+                "expectimax:depth=1,min_probability=0.001,heuristic=weighted,weights={}",
+                weights.display()
+            ),
+        ] {
+            let spec = s.parse::<AgentSpec>().unwrap();
+            assert_eq!(spec.to_string(), s);
+            let result = crate::eval::play(&spec, 3);
+            assert!(result.score > 0);
+        }
+        assert!(format!("learned:path={},depth=0", value.display())
+            .parse::<AgentSpec>()
+            .is_err());
+        std::fs::remove_file(&ntuple).unwrap();
+        std::fs::remove_file(&value).unwrap();
+        std::fs::remove_file(&weights).unwrap();
+        assert!(format!(
+            "expectimax:heuristic=weighted,weights={}",
+            weights.display()
+        )
+        .parse::<AgentSpec>()
+        .is_err());
+        assert!(format!("ntuple:path={}", ntuple.display())
+            .parse::<AgentSpec>()
+            .is_err());
+    }
+}
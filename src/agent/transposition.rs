@@ -0,0 +1,136 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+/// A fixed-size cache of search results, shared between the threads of a search.
+///
+/// Positions are stored by key, with the depth they were searched to and the probability of
+/// reaching them, since the search cuts short branches that become too unlikely. A result is only
+/// reused for a search that is no deeper and reached the position no more likely, as the stored
+/// search then looked at least as far. Each key maps to a single slot, so the table never grows:
+/// a new result takes over its slot, unless the slot holds the same position searched further.
+pub struct TranspositionTable {
+    slots: Vec<Mutex<Option<Entry>>>,
+    // slots filled, counted as they fill so that reading it doesn't lock every slot
+    len: AtomicUsize,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    key: u64,
+    depth: usize,
+    probability: f32,
+    value: f32,
+}
+
+impl Entry {
+    /// Whether this entry was searched at least as far as a search of `key` to `depth`, reached
+    /// with `probability`.
+    fn covers(&self, key: u64, depth: usize, probability: f32) -> bool {
+        self.key == key && self.depth >= depth && self.probability >= probability
+    }
+}
+
+impl TranspositionTable {
+    /// A table holding up to `capacity` positions.
+    ///
+    /// # Panics
+    /// If `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "a transposition table needs at least one slot"
+        );
+        TranspositionTable {
+            slots: (0..capacity).map(|_| Mutex::new(None)).collect(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, key: u64) -> &Mutex<Option<Entry>> {
+        // keys of small boards are the packed cells, so spread them before picking a slot
+        let hash = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        &self.slots[((hash >> 32) ^ hash) as usize % self.slots.len()]
+    }
+
+    /// The stored value of a position, if it was searched at least `depth` deep and reached with
+    /// at least `probability`.
+    pub fn get(&self, key: u64, depth: usize, probability: f32) -> Option<f32> {
+        let slot = self.slot(key).lock().unwrap();
+        slot.filter(|e| e.covers(key, depth, probability))
+            .map(|e| e.value)
+    }
+
+    pub fn insert(&self, key: u64, depth: usize, probability: f32, value: f32) {
+        let mut slot = self.slot(key).lock().unwrap();
+        match *slot {
+            Some(e) if e.covers(key, depth, probability) => return,
+            Some(_) => {}
+            None => {
+                self.len.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        *slot = Some(Entry {
+            key,
+            depth,
+            probability,
+            value,
+        });
+    }
+
+    /// Number of positions stored.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot.get_mut().unwrap() = None;
+        }
+        *self.len.get_mut() = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let mut table = TranspositionTable::new(16);
+        assert_eq!(table.get(7, 1, 1.0), None);
+        table.insert(7, 2, 1.0, 1.5);
+        assert_eq!(table.get(7, 1, 1.0), Some(1.5));
+        assert_eq!(table.get(7, 2, 1.0), Some(1.5));
+        assert_eq!(table.get(7, 3, 1.0), None);
+
+        // a shallower result doesn't replace a deeper one of the same position
+        table.insert(7, 1, 1.0, 3.0);
+        assert_eq!(table.get(7, 2, 1.0), Some(1.5));
+        table.insert(7, 3, 1.0, 3.0);
+        assert_eq!(table.get(7, 3, 1.0), Some(3.0));
+
+        // nor is a result reused by a search that reached the position more likely, since that
+        // search would cut fewer branches short
+        table.insert(8, 2, 0.5, 2.0);
+        assert_eq!(table.get(8, 2, 0.25), Some(2.0));
+        assert_eq!(table.get(8, 2, 1.0), None);
+        table.insert(8, 2, 1.0, 2.5);
+        assert_eq!(table.get(8, 2, 0.25), Some(2.5));
+
+        assert_eq!(table.len(), 2);
+        table.insert(9, 1, 1.0, 2.0);
+        assert_eq!(table.len(), 3);
+        table.clear();
+        assert!(table.is_empty());
+    }
+}
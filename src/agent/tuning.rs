@@ -0,0 +1,291 @@
+//! Offline tuning of [`WeightedSum`] weights with a genetic algorithm.
+//!
+//! Each candidate weight vector drives an [`Expectimax`] agent through a batch of seeded games,
+//! and its fitness is the mean score. All candidates of a generation play the same seeds, so
+//! they are compared on equal terms, and every generation draws new seeds, so the weights don't
+//! overfit a few lucky games. The best of each generation then plays a set of held-out seeds,
+//! the same for every generation, and the weights that score best there are the ones returned,
+//! rather than whichever generation happened to draw the easiest games.
+
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::{
+    eval,
+    game::{rng::Rng, Game},
+};
+
+use super::{
+    expectimax::{Expectimax, ExpectimaxParams},
+    heuristic::{Feature, WeightedSum},
+    Agent,
+};
+
+#[derive(Debug, Clone)]
+pub struct TuningParams {
+    pub seed: u64,
+    /// Candidates per generation.
+    pub population: usize,
+    pub generations: usize,
+    /// Games each candidate plays per generation.
+    pub games: usize,
+    /// Games the best candidate of each generation plays on the held-out seeds.
+    pub holdout_games: usize,
+    /// How many of the best candidates carry over unchanged to the next generation.
+    pub elites: usize,
+    /// Chance of each weight of a child being mutated.
+    pub mutation_rate: f32,
+    /// Standard deviation of a mutation, relative to the size of the weight.
+    pub mutation_scale: f32,
+    /// How many moves the agent looks ahead.
+    pub depth: usize,
+    /// Games are cut short after this many moves, so that good candidates don't take forever,
+    /// and so that a move that leaves the board as it was can't keep a game going.
+    pub max_moves: usize,
+    /// The weights the first generation is bred from.
+    pub start: WeightedSum,
+}
+
+impl Default for TuningParams {
+    fn default() -> Self {
+        TuningParams {
+            seed: fastrand::u64(..),
+            population: 16,
+            generations: 20,
+            games: 8,
+            holdout_games: 16,
+            elites: 2,
+            mutation_rate: 0.3,
+            mutation_scale: 0.5,
+            depth: 1,
+            max_moves: 2000,
+            start: WeightedSum::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub weights: WeightedSum,
+    /// Mean score over the games it was scored on: those of its generation while tuning, or the
+    /// held-out games once returned by [`tune`].
+    pub fitness: f32,
+}
+
+/// The result of one generation, reported while tuning.
+#[derive(Debug, Clone)]
+pub struct Generation {
+    /// Counting from 0.
+    pub index: usize,
+    pub best: Candidate,
+    pub mean_fitness: f32,
+    /// Mean score of `best` on the held-out seeds.
+    pub holdout_fitness: f32,
+    /// Whether `best` scores higher on the held-out seeds than the best of every earlier
+    /// generation.
+    pub improved: bool,
+}
+
+/// Plays one game with the given weights and returns its score.
+pub fn play_game(weights: &WeightedSum, seed: u64, depth: usize, max_moves: usize) -> usize {
+    let params = ExpectimaxParams {
+        seed: eval::agent_seed(seed),
+        depth,
+        heuristic: Arc::new(weights.clone()),
+        // games are short and many run at once, so a table would cost more than it saves
+        table_size: 0,
+        ..ExpectimaxParams::default()
+    };
+    let mut agent = Expectimax::new_with(Game::new_seeded(seed), params);
+    for _ in 0..max_moves {
+        if agent.get_game().game_over() {
+            break;
+        }
+        agent.make_move();
+    }
+    *agent.get_game().get_score()
+}
+
+/// Evolves weights for `params.generations` generations, calling `on_generation` after each
+/// one. Tuning stops early if `on_generation` returns `false`. Returns the best of the
+/// generations' best candidates on the held-out seeds, or the starting weights if no generation
+/// was played.
+pub fn tune(
+    params: &TuningParams,
+    mut on_generation: impl FnMut(&Generation) -> bool,
+) -> Candidate {
+    assert!(
+        params.population > params.elites,
+        "the population must be larger than the elites"
+    );
+    assert!(
+        params.games > 0 && params.holdout_games > 0,
+        "candidates must play at least one game"
+    );
+    let mut rng = Rng::new(params.seed);
+    let holdout = (0..params.holdout_games)
+        .map(|_| rng.u64())
+        .collect::<Vec<_>>();
+    let mut population = (0..params.population)
+        .map(|i| match i {
+            0 => params.start.clone(),
+            _ => mutate(&params.start, 1.0, params.mutation_scale, &mut rng),
+        })
+        .collect::<Vec<_>>();
+
+    let mut best: Option<Candidate> = None;
+    for index in 0..params.generations {
+        let seeds = (0..params.games).map(|_| rng.u64()).collect::<Vec<_>>();
+        let mut ranked = evaluate(&population, &seeds, params);
+        ranked.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+
+        let held_out = evaluate(&[ranked[0].weights.clone()], &holdout, params).remove(0);
+        let improved = match &best {
+            Some(b) => held_out.fitness > b.fitness,
+            None => true,
+        };
+        if improved {
+            best = Some(held_out.clone());
+        }
+        let generation = Generation {
+            index,
+            best: ranked[0].clone(),
+            mean_fitness: ranked.iter().map(|c| c.fitness).sum::<f32>() / ranked.len() as f32,
+            holdout_fitness: held_out.fitness,
+            improved,
+        };
+        if !on_generation(&generation) {
+            break;
+        }
+
+        population = ranked[..params.elites]
+            .iter()
+            .map(|c| c.weights.clone())
+            .collect();
+        while population.len() < params.population {
+            let a = tournament(&ranked, &mut rng);
+            let b = tournament(&ranked, &mut rng);
+            let child = crossover(a, b, &mut rng);
+            population.push(mutate(
+                &child,
+                params.mutation_rate,
+                params.mutation_scale,
+                &mut rng,
+            ));
+        }
+    }
+    best.unwrap_or_else(|| Candidate {
+        weights: params.start.clone(),
+        fitness: 0.0,
+    })
+}
+
+/// The fitness of every candidate, playing all games of all candidates in parallel.
+fn evaluate(population: &[WeightedSum], seeds: &[u64], params: &TuningParams) -> Vec<Candidate> {
+    let games = population
+        .iter()
+        .flat_map(|w| seeds.iter().map(move |seed| (w, *seed)))
+        .collect::<Vec<_>>();
+    let scores = games
+        .par_iter()
+        .map(|(w, seed)| play_game(w, *seed, params.depth, params.max_moves))
+        .collect::<Vec<_>>();
+
+    population
+        .iter()
+        .zip(scores.chunks(seeds.len()))
+        .map(|(w, scores)| Candidate {
+            weights: w.clone(),
+            fitness: scores.iter().sum::<usize>() as f32 / seeds.len() as f32,
+        })
+        .collect()
+}
+
+/// Picks the fittest of three random candidates from a ranked population.
+fn tournament<'a>(ranked: &'a [Candidate], rng: &mut Rng) -> &'a WeightedSum {
+    let i = (0..3).map(|_| rng.usize(0..ranked.len())).min().unwrap();
+    &ranked[i].weights
+}
+
+/// Takes each weight from either parent at random.
+fn crossover(a: &WeightedSum, b: &WeightedSum, rng: &mut Rng) -> WeightedSum {
+    let mut child = a.clone();
+    for f in Feature::iter() {
+        if rng.f32() < 0.5 {
+            child.weights[f] = b.weights[f];
+        }
+    }
+    child
+}
+
+/// Adds gaussian noise to each weight with probability `rate`. Weights near zero are moved as
+/// if they were 0.1, so they can still grow.
+fn mutate(weights: &WeightedSum, rate: f32, scale: f32, rng: &mut Rng) -> WeightedSum {
+    let mut mutated = weights.clone();
+    for f in Feature::iter() {
+        if rng.f32() < rate {
+            let w = mutated.weights[f];
+            mutated.weights[f] = w + normal(rng) * scale * w.abs().max(0.1);
+        }
+    }
+    mutated
+}
+
+/// A sample of the standard normal distribution, by the Box-Muller transform.
+fn normal(rng: &mut Rng) -> f32 {
+    let u1 = 1.0 - rng.f32();
+    let u2 = rng.f32();
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tune() {
+        let params = TuningParams {
+            seed: 1,
+            population: 4,
+            generations: 3,
+            games: 2,
+            elites: 1,
+            max_moves: 30,
+            ..TuningParams::default()
+        };
+        let mut generations = vec![];
+        let best = tune(&params, |g| {
+            generations.push(g.clone());
+            g.index < 1
+        });
+        assert_eq!(generations.len(), 2);
+        // the winner is picked on the held-out seeds, not on each generation's own
+        let fittest = generations
+            .iter()
+            .max_by(|a, b| a.holdout_fitness.total_cmp(&b.holdout_fitness))
+            .unwrap();
+        assert_eq!(best.fitness, fittest.holdout_fitness);
+        assert!(generations[0].improved);
+        assert_eq!(
+            generations[1].improved,
+            generations[1].holdout_fitness > generations[0].holdout_fitness
+        );
+
+        // the same seed tunes the same weights
+        let again = tune(&params, |g| g.index < 1);
+        assert_eq!(again.weights, best.weights);
+    }
+
+    #[test]
+    fn test_negative_weights() {
+        // every position scores below 0, which must not stall the game
+        let weights = WeightedSum {
+            bias: 0.0,
+            weights: enum_map::enum_map! { Feature::EmptyCells => -100.0, _ => 0.0 },
+        };
+        let score = play_game(&weights, 1, 1, 2000);
+        assert!(score > 0);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    game::{Game, Move},
+    game::{history::RecordedGame, outcome::MoveOutcome, Game, Move},
     tui::IntAction,
 };
 
@@ -9,7 +9,8 @@ use crossterm::event::{Event, KeyCode};
 use std::{thread, time::Duration};
 
 pub struct UserAgent {
-    game: Game,
+    game: RecordedGame,
+    last_outcome: Option<MoveOutcome>,
 }
 
 impl UserAgent {
@@ -17,7 +18,15 @@ impl UserAgent {
     where
         Self: Sized,
     {
-        UserAgent { game }
+        UserAgent::resume(RecordedGame::new(game))
+    }
+
+    /// Continues a saved game, with its moves still undoable.
+    pub fn resume(game: RecordedGame) -> Self {
+        UserAgent {
+            game,
+            last_outcome: None,
+        }
     }
 }
 
@@ -31,19 +40,32 @@ impl Agent for UserAgent {
     }
 
     fn get_game(&self) -> &Game {
-        &self.game
+        self.game.game()
     }
 }
 
 impl TuiAgent for UserAgent {
-    fn messages(&self) -> Vec<tui::text::Spans> {
-        vec![tui::text::Spans::from("Use WASD or arrow keys to move.")]
+    fn messages(&self) -> Vec<tui::text::Spans<'_>> {
+        vec![
+            tui::text::Spans::from("Use WASD or arrow keys to move."),
+            tui::text::Spans::from("Press u to undo and r to redo."),
+        ]
     }
 
     fn get_input(&mut self, event: &Event) -> IntAction {
         let Ok(keyboard_move) = (match event {
             Event::Key(key) => match key.code {
                 KeyCode::Char('q') => return IntAction::Exit,
+                KeyCode::Char('u') => {
+                    self.game.undo();
+                    self.last_outcome = None;
+                    return IntAction::Continue;
+                }
+                KeyCode::Char('r') => {
+                    self.game.redo();
+                    self.last_outcome = None;
+                    return IntAction::Continue;
+                }
                 KeyCode::Char('w') => Ok(Move::Up),
                 KeyCode::Char('a') => Ok(Move::Left),
                 KeyCode::Char('s') => Ok(Move::Down),
@@ -60,7 +82,15 @@ impl TuiAgent for UserAgent {
         };
 
         // synchronously update the game
-        self.game.make_move(keyboard_move);
+        self.last_outcome = Some(self.game.make_move(keyboard_move));
         IntAction::Continue
     }
+
+    fn last_outcome(&self) -> Option<&MoveOutcome> {
+        self.last_outcome.as_ref()
+    }
+
+    fn recorded_game(&self) -> Option<&RecordedGame> {
+        Some(&self.game)
+    }
 }
@@ -1,7 +1,502 @@
-use ai_2048;
-use std::error::Error;
+use std::{
+    error::Error,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use ai_2048::{
+    agent::{
+        expectimax::{Expectimax, ExpectimaxParams},
+        heuristic::WeightedSum,
+        learned::{generate_dataset, RidgeRegression},
+        ntuple::{self, NTupleNetwork, TdParams},
+        spec::AgentSpec,
+        tuning::{tune, TuningParams},
+        user::UserAgent,
+    },
+    eval::{self, compare::compare, EvalParams, Evaluation},
+    game::{replay::Replay, Game},
+    storage,
+};
+use clap::{builder::RangedU64ValueParser, value_parser, Arg, ArgMatches, Command};
+use linfa::traits::Fit;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    ai_2048::tui::start()?;
+    let matches = Command::new("ai-2048")
+        .about("Play 2048, or watch agents play it. Starts the TUI when run without a command.")
+        .subcommand(
+            Command::new("play")
+                .about("Starts the TUI, straight into a game if an agent or seed is given")
+                .arg(
+                    agent_arg()
+                        .help("Agent to watch play, such as expectimax:depth=3. Play by keyboard if not given")
+                        .long("agent")
+                        .takes_value(true),
+                )
+                .arg(seed_arg("Seed of the game"))
+                .arg(path_arg(
+                    "save",
+                    "RON file that Ctrl+S saves to and the menu resumes from, instead of the data directory",
+                )),
+        )
+        .subcommand(
+            Command::new("solve")
+                .about("Plays one game with an agent without the TUI, printing its progress")
+                .arg(
+                    agent_arg()
+                        .help("The agent to play with")
+                        .default_value("expectimax"),
+                )
+                .arg(seed_arg("Seed of the game"))
+                .arg(
+                    Arg::new("every")
+                        .help("Moves between progress lines")
+                        .long("every")
+                        .takes_value(true)
+                        .default_value("100")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(path_arg("record", "Writes a record of the game, for replay, to a RON file")),
+        )
+        .subcommand(
+            Command::new("eval")
+                .about("Plays seeded games with each agent and reports how they did")
+                .arg(
+                    agent_arg()
+                        .id("agents")
+                        .help("Agents to evaluate, such as random, corner or expectimax:depth=3")
+                        .required(true)
+                        .multiple_values(true),
+                )
+                .arg(games_arg("Games played by each agent", Some("100")))
+                .arg(seed_arg("Seed of the games"))
+                .arg(path_arg("json", "Writes the results to a JSON file"))
+                .arg(path_arg("csv", "Writes every game to a CSV file")),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Plays two agents on the same seeded games, and tests whether the candidate scores differently")
+                .arg(
+                    agent_arg()
+                        .id("baseline")
+                        .help("The agent to compare against")
+                        .required(true),
+                )
+                .arg(
+                    agent_arg()
+                        .id("candidate")
+                        .help("The agent being tried out")
+                        .required(true),
+                )
+                .arg(games_arg("Games played by each agent", Some("100")))
+                .arg(seed_arg("Seed of the games"))
+                .arg(
+                    Arg::new("confidence")
+                        .help("Confidence level of the interval and the test")
+                        .long("confidence")
+                        .takes_value(true)
+                        .default_value("0.95")
+                        .value_parser(value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("per-seed")
+                        .help("Prints the scores of both agents on every seed")
+                        .long("per-seed"),
+                )
+                .arg(path_arg("json", "Writes the comparison to a JSON file"))
+                .arg(path_arg("csv", "Writes the scores on every seed to a CSV file")),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Replays a game record, checking that every move is legal")
+                .arg(
+                    Arg::new("record")
+                        .help("The RON file of the record")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("verify")
+                        .help("Fails unless the record reaches exactly this score")
+                        .long("verify")
+                        .takes_value(true)
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("steps")
+                        .help("Prints the board after every move")
+                        .long("steps"),
+                ),
+        )
+        .subcommand(
+            Command::new("train")
+                .about("Trains what the learning agents play with, without the TUI")
+                .arg(
+                    Arg::new("model")
+                        .help("weights tunes the Expectimax heuristic, ntuple trains the n-tuple network, value learns the value function")
+                        .required(true)
+                        .value_parser(["weights", "ntuple", "value"]),
+                )
+                .arg(games_arg(
+                    "Generations for weights, or games of self-play for ntuple and value",
+                    None,
+                ))
+                .arg(seed_arg("Seed of the training"))
+                .arg(path_arg(
+                    "out",
+                    "Where to save the result, the TUI's data directory if not given",
+                )),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        Some(("play", matches)) => play(matches),
+        Some(("solve", matches)) => solve(matches),
+        Some(("eval", matches)) => evaluate(matches),
+        Some(("compare", matches)) => compare_agents(matches),
+        Some(("replay", matches)) => replay(matches),
+        Some(("train", matches)) => train(matches),
+        _ => ai_2048::tui::start(None),
+    }
+}
+
+fn agent_arg() -> Arg<'static> {
+    Arg::new("agent").value_parser(value_parser!(AgentSpec))
+}
+
+fn seed_arg(help: &'static str) -> Arg<'static> {
+    Arg::new("seed")
+        .help(help)
+        .long("seed")
+        .takes_value(true)
+        .value_parser(value_parser!(u64))
+}
+
+/// Games to play, at least one. Without a default, the command picks one.
+fn games_arg(help: &'static str, default: Option<&'static str>) -> Arg<'static> {
+    let arg = Arg::new("games")
+        .help(help)
+        .short('n')
+        .long("games")
+        .takes_value(true)
+        .value_parser(RangedU64ValueParser::<usize>::new().range(1..));
+    match default {
+        Some(default) => arg.default_value(default),
+        None => arg,
+    }
+}
+
+fn path_arg(name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .help(help)
+        .long(name)
+        .takes_value(true)
+        .value_parser(value_parser!(PathBuf))
+}
+
+/// The given seed, or a random one.
+fn seed(matches: &ArgMatches) -> u64 {
+    matches
+        .get_one::<u64>("seed")
+        .copied()
+        .unwrap_or_else(|| fastrand::u64(..))
+}
+
+fn play(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let seed = matches.get_one::<u64>("seed").copied();
+    let save = matches.get_one::<PathBuf>("save").cloned();
+    match (matches.get_one::<AgentSpec>("agent"), seed) {
+        (Some(spec), _) => {
+            let seed = seed.unwrap_or_else(|| fastrand::u64(..));
+            let agent = spec.build(Game::new_seeded(seed), eval::agent_seed(seed));
+            ai_2048::tui::start_game(agent, &format!("Solve ({})", spec.name()), save)
+        }
+        (None, Some(seed)) => ai_2048::tui::start_game(
+            Box::new(UserAgent::new(Game::new_seeded(seed))),
+            "Play (Keyboard)",
+            save,
+        ),
+        (None, None) => ai_2048::tui::start(save),
+    }
+}
+
+fn solve(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let spec = matches.get_one::<AgentSpec>("agent").unwrap();
+    let seed = seed(matches);
+    let every = (*matches.get_one::<usize>("every").unwrap()).max(1);
+    println!("Solving with {} and seed {}", spec, seed);
+
+    let mut game = Game::new_seeded(seed);
+    let record = matches.get_one::<PathBuf>("record");
+    let mut replay = Replay::new(seed, game.width(), game.height(), *game.get_rules());
+    let mut agent = spec.build(game, eval::agent_seed(seed));
+    let mut next_report = every;
+    while !agent.get_game().game_over() {
+        agent.make_move();
+        // the agent plays a copy of `game`, spawns and all, so exactly the move it played takes
+        // this one to the same place; recording that move doesn't rely on the agent reporting it
+        let played = game.available_moves().find(|m| {
+            let mut next = game;
+            next.make_move(*m);
+            next == *agent.get_game()
+        });
+        let Some(m) = played else {
+            return Err(format!(
+                "{} went from\n{}\nto\n{}\nwhich no move does",
+                spec,
+                game,
+                agent.get_game()
+            )
+            .into());
+        };
+        game.make_move(m);
+        replay.push(m);
+        if *game.get_num_moves() >= next_report {
+            next_report += every;
+            println!(
+                "Move {}: score {}, max tile {}",
+                game.get_num_moves(),
+                game.get_score(),
+                game.max_tile()
+            );
+        }
+    }
+
+    let game = agent.get_game();
+    println!("{}", game);
+    println!(
+        "Game over after {} moves: score {}, max tile {}",
+        game.get_num_moves(),
+        game.get_score(),
+        game.max_tile()
+    );
+    if let Some(path) = record {
+        replay.save(path)?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn eval_params(matches: &ArgMatches) -> EvalParams {
+    EvalParams {
+        games: *matches.get_one::<usize>("games").unwrap(),
+        seed: seed(matches),
+    }
+}
+
+/// Prints how many games an agent has played, over the same line.
+fn show_progress(spec: &AgentSpec, played: &AtomicUsize, games: usize) {
+    let played = played.fetch_add(1, Ordering::Relaxed) + 1;
+    eprint!("\r{}: {} of {} games", spec.name(), played, games);
+    if played == games {
+        eprintln!();
+    }
+    std::io::stderr().flush().ok();
+}
+
+fn evaluate(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let params = eval_params(matches);
+    println!("Evaluating with seed {}", params.seed);
+
+    let mut evaluations = vec![];
+    for spec in matches.get_many::<AgentSpec>("agents").unwrap() {
+        let played = AtomicUsize::new(0);
+        let results = eval::evaluate(spec, &params, |_| {
+            show_progress(spec, &played, params.games)
+        });
+        let evaluation = Evaluation::new(spec, results);
+        println!("{}", evaluation.summary);
+        evaluations.push(evaluation);
+    }
+
+    if let Some(path) = matches.get_one::<PathBuf>("json") {
+        eval::write_json(&evaluations, path)?;
+        println!("Wrote {}", path.display());
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("csv") {
+        eval::write_csv(&evaluations, path)?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn compare_agents(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let params = eval_params(matches);
+    let confidence = *matches.get_one::<f64>("confidence").unwrap();
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return Err(format!("confidence must be between 0 and 1, found {}", confidence).into());
+    }
+    let baseline = matches.get_one::<AgentSpec>("baseline").unwrap();
+    let candidate = matches.get_one::<AgentSpec>("candidate").unwrap();
+    println!("Comparing with seed {}", params.seed);
+
+    let baseline_played = AtomicUsize::new(0);
+    let candidate_played = AtomicUsize::new(0);
+    let comparison = compare(baseline, candidate, &params, confidence, |spec, _| {
+        // both may be the same agent, so tell them apart by address
+        let played = if std::ptr::eq(spec, baseline) {
+            &baseline_played
+        } else {
+            &candidate_played
+        };
+        show_progress(spec, played, params.games)
+    });
+
+    if matches.contains_id("per-seed") {
+        println!(
+            "{:>20} {:>9} {:>9} {:>9}",
+            "seed", "baseline", "candidate", "diff"
+        );
+        for pair in &comparison.pairs {
+            println!(
+                "{:>20} {:>9} {:>9} {:>+9}",
+                pair.seed,
+                pair.baseline,
+                pair.candidate,
+                pair.difference()
+            );
+        }
+    }
+    println!("{}", comparison);
+
+    if let Some(path) = matches.get_one::<PathBuf>("json") {
+        comparison.write_json(path)?;
+        println!("Wrote {}", path.display());
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("csv") {
+        comparison.write_csv(path)?;
+        println!("Wrote {}", path.display());
+    }
+    if comparison.is_regression() {
+        return Err("the candidate is a regression".into());
+    }
+    Ok(())
+}
+
+fn replay(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = matches.get_one::<PathBuf>("record").unwrap();
+    let replay = Replay::load(path)?;
+    println!(
+        "Replaying {} moves on a {}x{} board, seed {}",
+        replay.moves().len(),
+        replay.width(),
+        replay.height(),
+        replay.seed()
+    );
+
+    if matches.contains_id("steps") {
+        let states = replay.states()?;
+        println!("Start:\n{}\n", states[0]);
+        for (m, game) in replay.moves().iter().zip(&states[1..]) {
+            println!(
+                "Move {} ({}), score {}:\n{}\n",
+                game.get_num_moves(),
+                m,
+                game.get_score(),
+                game
+            );
+        }
+    }
+
+    let game = match matches.get_one::<usize>("verify") {
+        Some(score) => replay.verify(*score)?,
+        None => replay.replay()?,
+    };
+    println!("{}", game);
+    println!(
+        "Final score {} after {} moves, max tile {}{}",
+        game.get_score(),
+        game.get_num_moves(),
+        game.max_tile(),
+        if game.game_over() { ", game over" } else { "" }
+    );
+    Ok(())
+}
+
+fn train(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let seed = seed(matches);
+    let games = matches.get_one::<usize>("games").copied();
+    let out = matches.get_one::<PathBuf>("out").cloned();
+    match matches.get_one::<String>("model").unwrap().as_str() {
+        "weights" => {
+            let path = out.map_or_else(storage::weights_path, Ok)?;
+            let mut params = TuningParams {
+                seed,
+                ..TuningParams::default()
+            };
+            params.generations = games.unwrap_or(params.generations);
+            let mut saved = Ok(());
+            tune(&params, |generation| {
+                println!(
+                    "Generation {} of {}: best {:.0} ({:.0} held out), mean {:.0}",
+                    generation.index + 1,
+                    params.generations,
+                    generation.best.fitness,
+                    generation.holdout_fitness,
+                    generation.mean_fitness
+                );
+                if generation.improved {
+                    saved = generation.best.weights.save(&path);
+                }
+                saved.is_ok()
+            });
+            saved?;
+            println!("Saved the best weights to {}", path.display());
+        }
+        "ntuple" => {
+            let path = out.map_or_else(storage::ntuple_path, Ok)?;
+            // picks up from the saved network, like the TUI does
+            let mut network =
+                NTupleNetwork::load(&path).unwrap_or_else(|_| NTupleNetwork::new_4x4());
+            let mut params = TdParams {
+                seed,
+                ..TdParams::default()
+            };
+            params.games = games.unwrap_or(params.games);
+            let mut saved = Ok(());
+            ntuple::train(&mut network, &params, |report, network| {
+                println!(
+                    "{} games: mean score {:.0}, best {}, {:.1}% won",
+                    report.games,
+                    report.mean_score,
+                    report.max_score,
+                    100.0 * report.win_rate
+                );
+                saved = network.save(&path);
+                saved.is_ok()
+            });
+            saved?;
+            println!("Saved the network to {}", path.display());
+        }
+        "value" => {
+            let path = out.map_or_else(storage::value_path, Ok)?;
+            let games = games.unwrap_or(200);
+            let weights = Arc::new(
+                storage::weights_path()
+                    .and_then(WeightedSum::load)
+                    .unwrap_or_default(),
+            );
+            println!("Playing {} games of self-play", games);
+            let dataset = generate_dataset(games, seed, |game, seed| {
+                let params = ExpectimaxParams {
+                    seed,
+                    depth: 1,
+                    heuristic: weights.clone(),
+                    table_size: 0,
+                    ..ExpectimaxParams::default()
+                };
+                Expectimax::new_with(game, params)
+            });
+            println!("Fitting to {} positions", dataset.targets().len());
+            let value = RidgeRegression::default().fit(&dataset)?;
+            value.save(&path)?;
+            println!("Saved the value function to {}", path.display());
+        }
+        _ => unreachable!(),
+    }
     Ok(())
 }
@@ -0,0 +1,432 @@
+//! A/B comparison of two agents on the same games.
+//!
+//! Both agents play the games of one evaluation, so each seed gives a pair of scores that differ
+//! only by the agents. Much of the spread in scores comes from the games themselves, and pairing
+//! cancels it out, so a paired t-test on the differences tells a real change from noise with far
+//! fewer games than comparing the two means would.
+
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::spec::AgentSpec;
+
+use super::{evaluate, Distribution, EvalParams, Evaluation, GameResult};
+
+/// The scores both agents made on one seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pair {
+    pub seed: u64,
+    pub baseline: usize,
+    pub candidate: usize,
+}
+
+impl Pair {
+    /// How much more the candidate scored.
+    pub fn difference(&self) -> f64 {
+        self.candidate as f64 - self.baseline as f64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verdict {
+    /// The candidate scores significantly more.
+    Improvement,
+    /// The candidate scores significantly less.
+    Regression,
+    /// The difference could be noise.
+    NoDifference,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comparison {
+    pub baseline: Evaluation,
+    pub candidate: Evaluation,
+    pub pairs: Vec<Pair>,
+    /// Of the candidate's score minus the baseline's, over the pairs.
+    pub difference: Distribution,
+    /// Confidence level of the interval, such as 0.95.
+    pub confidence: f64,
+    /// Confidence interval of the mean difference, or `None` with fewer than two pairs, which
+    /// say nothing of the spread.
+    pub interval: Option<(f64, f64)>,
+    /// The paired t statistic, or `None` where it is undefined: with fewer than two pairs, or
+    /// when every pair differs by the same amount.
+    pub t: Option<f64>,
+    /// Two-sided p-value of the paired t-test, against no difference.
+    pub p_value: f64,
+    pub verdict: Verdict,
+}
+
+impl Comparison {
+    /// Compares the results of two agents on the same seeds. The verdict is significant at a
+    /// level of `1 - confidence`.
+    ///
+    /// # Panics
+    /// If the results aren't of the same seeds, in the same order, or `confidence` isn't between
+    /// 0 and 1.
+    pub fn new(baseline: Evaluation, candidate: Evaluation, confidence: f64) -> Self {
+        assert!(
+            confidence > 0.0 && confidence < 1.0,
+            "confidence must be between 0 and 1, found {}",
+            confidence
+        );
+        let pairs = pair_up(&baseline.results, &candidate.results);
+        let differences = pairs.iter().map(Pair::difference).collect::<Vec<_>>();
+        let difference = Distribution::of(&differences);
+        let n = differences.len() as f64;
+        let df = n - 1.0;
+
+        let (t, p_value, half_width) = if differences.len() < 2 {
+            (None, 1.0, None)
+        } else if difference.stddev == 0.0 {
+            // every pair differs by the same amount, which is certain unless it is 0
+            let p_value = if difference.mean == 0.0 { 1.0 } else { 0.0 };
+            (None, p_value, Some(0.0))
+        } else {
+            let standard_error = difference.stddev / n.sqrt();
+            let t = difference.mean / standard_error;
+            (
+                Some(t),
+                t_two_sided_p(t, df),
+                Some(t_quantile(confidence, df) * standard_error),
+            )
+        };
+
+        let verdict = if p_value < 1.0 - confidence {
+            if difference.mean > 0.0 {
+                Verdict::Improvement
+            } else {
+                Verdict::Regression
+            }
+        } else {
+            Verdict::NoDifference
+        };
+
+        Comparison {
+            baseline,
+            candidate,
+            pairs,
+            difference,
+            confidence,
+            interval: half_width.map(|h| (difference.mean - h, difference.mean + h)),
+            t,
+            p_value,
+            verdict,
+        }
+    }
+
+    pub fn is_regression(&self) -> bool {
+        self.verdict == Verdict::Regression
+    }
+
+    /// Writes the comparison, with both evaluations and every pair, as JSON.
+    pub fn write_json(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Writes every pair as CSV, one row per seed.
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "seed,baseline,candidate,difference")?;
+        for pair in &self.pairs {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                pair.seed,
+                pair.baseline,
+                pair.candidate,
+                pair.difference()
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn pair_up(baseline: &[GameResult], candidate: &[GameResult]) -> Vec<Pair> {
+    assert_eq!(
+        baseline.len(),
+        candidate.len(),
+        "both agents must play the same games"
+    );
+    baseline
+        .iter()
+        .zip(candidate)
+        .map(|(b, c)| {
+            assert_eq!(b.seed, c.seed, "both agents must play the same games");
+            Pair {
+                seed: b.seed,
+                baseline: b.score,
+                candidate: c.score,
+            }
+        })
+        .collect()
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "baseline:  {}", self.baseline.summary)?;
+        writeln!(f, "candidate: {}", self.candidate.summary)?;
+        let wins = self
+            .pairs
+            .iter()
+            .filter(|p| p.candidate > p.baseline)
+            .count();
+        let losses = self
+            .pairs
+            .iter()
+            .filter(|p| p.candidate < p.baseline)
+            .count();
+        writeln!(
+            f,
+            "Candidate minus baseline over {} games: mean {:.1}, median {:.1}, stddev {:.1}, better on {}, worse on {}",
+            self.pairs.len(),
+            self.difference.mean,
+            self.difference.median,
+            self.difference.stddev,
+            wins,
+            losses
+        )?;
+        match self.interval {
+            Some((low, high)) => writeln!(
+                f,
+                "{:.0}% confidence interval of the mean difference: [{:.1}, {:.1}]",
+                100.0 * self.confidence,
+                low,
+                high
+            )?,
+            None => writeln!(f, "Too few games for a confidence interval")?,
+        }
+        match self.t {
+            Some(t) => writeln!(f, "Paired t-test: t = {:.3}, p = {:.4}", t, self.p_value)?,
+            None => writeln!(f, "Paired t-test: t undefined, p = {:.4}", self.p_value)?,
+        }
+        write!(
+            f,
+            "{}",
+            match self.verdict {
+                Verdict::Improvement => "The candidate is an improvement",
+                Verdict::Regression => "REGRESSION: the candidate scores significantly less",
+                Verdict::NoDifference => "No significant difference",
+            }
+        )
+    }
+}
+
+/// Plays both agents on the games of `params`, and compares them.
+pub fn compare(
+    baseline: &AgentSpec,
+    candidate: &AgentSpec,
+    params: &EvalParams,
+    confidence: f64,
+    on_game: impl Fn(&AgentSpec, &GameResult) + Sync,
+) -> Comparison {
+    let baseline_results = evaluate(baseline, params, |r| on_game(baseline, r));
+    let candidate_results = evaluate(candidate, params, |r| on_game(candidate, r));
+    Comparison::new(
+        Evaluation::new(baseline, baseline_results),
+        Evaluation::new(candidate, candidate_results),
+        confidence,
+    )
+}
+
+/// Natural log of the gamma function, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // reflection, since the approximation holds for x >= 0.5
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |acc, (i, c)| {
+            acc + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// The regularized incomplete beta function `I_x(a, b)`.
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // the continued fraction converges quickly only on this side
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_fraction(1.0 - x, b, a) / b
+    }
+}
+
+/// The continued fraction of the incomplete beta function, by Lentz's method.
+fn beta_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-14 {
+            break;
+        }
+    }
+    h
+}
+
+/// The chance of a t statistic at least as far from 0 as `t`, with `df` degrees of freedom.
+fn t_two_sided_p(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / (df + t * t), df / 2.0, 0.5)
+}
+
+/// The `t` such that a t statistic with `df` degrees of freedom lies within `[-t, t]` with
+/// chance `confidence`.
+fn t_quantile(confidence: f64, df: f64) -> f64 {
+    let target = 1.0 - confidence;
+    // the p-value falls as t grows, so bisect for the t where it meets the target
+    let (mut low, mut high) = (0.0, 1.0);
+    while t_two_sided_p(high, df) > target {
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if t_two_sided_p(mid, df) > target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_t_distribution() {
+        // from tables of Student's t distribution
+        for (confidence, df, t) in [
+            (0.95, 1.0, 12.706),
+            (0.95, 10.0, 2.228),
+            (0.99, 30.0, 2.750),
+            (0.90, 1000.0, 1.646),
+        ] {
+            assert!((t_quantile(confidence, df) - t).abs() < 1e-3);
+            assert!((t_two_sided_p(t, df) - (1.0 - confidence)).abs() < 1e-4);
+        }
+        assert_eq!(t_two_sided_p(0.0, 5.0), 1.0);
+    }
+
+    #[test]
+    fn test_compare() {
+        let params = EvalParams { games: 30, seed: 4 };
+        let random = AgentSpec::Random;
+        let corner = "corner".parse::<AgentSpec>().unwrap();
+
+        let same = compare(&random, &random, &params, 0.95, |_, _| {});
+        assert!(same.pairs.iter().all(|p| p.difference() == 0.0));
+        assert_eq!(same.verdict, Verdict::NoDifference);
+        assert_eq!(same.interval, Some((0.0, 0.0)));
+        assert_eq!(same.t, None);
+
+        let better = compare(&random, &corner, &params, 0.95, |_, _| {});
+        assert_eq!(better.verdict, Verdict::Improvement);
+        assert!(better.interval.unwrap().0 > 0.0);
+        let worse = compare(&corner, &random, &params, 0.95, |_, _| {});
+        assert!(worse.is_regression());
+        assert!((worse.p_value - better.p_value).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "confidence must be between 0 and 1")]
+    fn test_confidence_range() {
+        let results = vec![];
+        Comparison::new(
+            Evaluation::new(&AgentSpec::Random, results.clone()),
+            Evaluation::new(&AgentSpec::Random, results),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn test_undefined_statistics_round_trip() {
+        let result = |seed, score| GameResult {
+            seed,
+            score,
+            moves: 10,
+            max_tile: 16,
+        };
+        let comparison = |baseline: Vec<GameResult>, candidate: Vec<GameResult>| {
+            Comparison::new(
+                Evaluation::new(&AgentSpec::Random, baseline),
+                Evaluation::new(&AgentSpec::Random, candidate),
+                0.95,
+            )
+        };
+
+        // a single game has no spread, and two games that differ by the same amount have none
+        let single = comparison(vec![result(1, 100)], vec![result(1, 120)]);
+        assert_eq!((single.t, single.interval), (None, None));
+        let constant = comparison(
+            vec![result(1, 100), result(2, 200)],
+            vec![result(1, 120), result(2, 220)],
+        );
+        assert_eq!(constant.t, None);
+        assert_eq!(constant.interval, Some((20.0, 20.0)));
+        assert_eq!(constant.verdict, Verdict::Improvement);
+
+        for comparison in [single, constant] {
+            let json = serde_json::to_string(&comparison).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Comparison>(&json).unwrap(),
+                comparison
+            );
+        }
+    }
+}
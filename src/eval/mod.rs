@@ -0,0 +1,293 @@
+//! Measures how strong agents are by playing many seeded games without the TUI.
+//!
+//! Game `i` of an evaluation is seeded the same for every agent, both its tile spawns and the
+//! agent's own randomness, so agents evaluated with the same seed face the same games. The two
+//! are seeded apart, see [`agent_seed`].
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+pub mod compare;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::spec::AgentSpec,
+    game::{rng::Rng, Game},
+};
+
+/// Tiles whose reach rate is reported.
+pub const REACH_TILES: [u32; 3] = [2048, 4096, 8192];
+
+#[derive(Debug, Clone)]
+pub struct EvalParams {
+    /// Games played by each agent.
+    pub games: usize,
+    /// Seed the seeds of the games are drawn from.
+    pub seed: u64,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            games: 100,
+            seed: fastrand::u64(..),
+        }
+    }
+}
+
+impl EvalParams {
+    /// The seed of every game, in order.
+    pub fn game_seeds(&self) -> Vec<u64> {
+        let mut rng = Rng::new(self.seed);
+        (0..self.games).map(|_| rng.u64()).collect()
+    }
+}
+
+/// How one game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameResult {
+    pub seed: u64,
+    pub score: usize,
+    pub moves: usize,
+    pub max_tile: u32,
+}
+
+/// The seed of an agent's own randomness in the game seeded with `seed`. It is drawn from the
+/// game's seed rather than being the seed itself, which would give the agent the very stream the
+/// game spawns tiles from.
+pub fn agent_seed(seed: u64) -> u64 {
+    Rng::new(seed).split().u64()
+}
+
+/// Plays a game seeded with `seed` to the end. A game is also stopped if the agent plays a move
+/// that doesn't change the board, since it would never end.
+pub fn play(spec: &AgentSpec, seed: u64) -> GameResult {
+    let game = Game::new_seeded(seed);
+    let mut agent = spec.build(game, agent_seed(seed));
+    while !agent.get_game().game_over() {
+        let moves = *agent.get_game().get_num_moves();
+        agent.make_move();
+        if *agent.get_game().get_num_moves() == moves {
+            break;
+        }
+    }
+    let game = agent.get_game();
+    GameResult {
+        seed,
+        score: *game.get_score(),
+        moves: *game.get_num_moves(),
+        max_tile: game.max_tile(),
+    }
+}
+
+/// Plays every game of an evaluation in parallel, calling `on_game` as each one ends. Results
+/// are in the order of [`EvalParams::game_seeds`].
+pub fn evaluate(
+    spec: &AgentSpec,
+    params: &EvalParams,
+    on_game: impl Fn(&GameResult) + Sync,
+) -> Vec<GameResult> {
+    params
+        .game_seeds()
+        .par_iter()
+        .map(|seed| {
+            let result = play(spec, *seed);
+            on_game(&result);
+            result
+        })
+        .collect()
+}
+
+/// Summary statistics of a set of values.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation, or 0 for fewer than two values.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    pub fn of(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Distribution::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n % 2 == 1 {
+            sorted[n / 2]
+        } else {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        };
+        let variance = if n > 1 {
+            sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+        } else {
+            0.0
+        };
+        Distribution {
+            mean,
+            median,
+            stddev: variance.sqrt(),
+            min: sorted[0],
+            max: sorted[n - 1],
+        }
+    }
+}
+
+/// How an agent did over an evaluation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub agent: String,
+    pub games: usize,
+    pub score: Distribution,
+    pub moves: Distribution,
+    /// How many games ended with each tile as the biggest.
+    pub max_tiles: BTreeMap<u32, usize>,
+    /// Share of games that reached each of [`REACH_TILES`].
+    pub reach: BTreeMap<u32, f64>,
+}
+
+impl Summary {
+    pub fn new(agent: &AgentSpec, results: &[GameResult]) -> Self {
+        let scores = results.iter().map(|r| r.score as f64).collect::<Vec<_>>();
+        let moves = results.iter().map(|r| r.moves as f64).collect::<Vec<_>>();
+        let mut max_tiles = BTreeMap::new();
+        for r in results {
+            *max_tiles.entry(r.max_tile).or_insert(0) += 1;
+        }
+        let reach = REACH_TILES
+            .iter()
+            .map(|t| {
+                let reached = results.iter().filter(|r| r.max_tile >= *t).count();
+                (*t, reached as f64 / results.len().max(1) as f64)
+            })
+            .collect();
+        Summary {
+            agent: agent.to_string(),
+            games: results.len(),
+            score: Distribution::of(&scores),
+            moves: Distribution::of(&moves),
+            max_tiles,
+            reach,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({} games)", self.agent, self.games)?;
+        for (name, d) in [("score", &self.score), ("moves", &self.moves)] {
+            writeln!(
+                f,
+                "  {:<6} mean {:.1}, median {:.1}, stddev {:.1}, min {}, max {}",
+                name, d.mean, d.median, d.stddev, d.min, d.max
+            )?;
+        }
+        let reach = self
+            .reach
+            .iter()
+            .map(|(t, r)| format!("{} {:.1}%", t, 100.0 * r))
+            .collect::<Vec<_>>();
+        writeln!(f, "  reached {}", reach.join(", "))?;
+        let tiles = self
+            .max_tiles
+            .iter()
+            .map(|(t, n)| format!("{}: {}", t, n))
+            .collect::<Vec<_>>();
+        write!(f, "  max tiles {}", tiles.join(", "))
+    }
+}
+
+/// An agent's summary along with every game it played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evaluation {
+    pub summary: Summary,
+    pub results: Vec<GameResult>,
+}
+
+impl Evaluation {
+    pub fn new(agent: &AgentSpec, results: Vec<GameResult>) -> Self {
+        Evaluation {
+            summary: Summary::new(agent, &results),
+            results,
+        }
+    }
+}
+
+/// Writes evaluations, summaries and games, as a JSON array.
+pub fn write_json(
+    evaluations: &[Evaluation],
+    path: impl AsRef<Path>,
+) -> Result<(), Box<dyn Error>> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, evaluations)?;
+    Ok(())
+}
+
+/// Writes every game of the evaluations as CSV, one row per game.
+pub fn write_csv(evaluations: &[Evaluation], path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "agent,seed,score,moves,max_tile")?;
+    for evaluation in evaluations {
+        // specs hold commas, so the agent is quoted
+        let agent = format!("\"{}\"", evaluation.summary.agent.replace('"', "\"\""));
+        for r in &evaluation.results {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                agent, r.seed, r.score, r.moves, r.max_tile
+            )?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution() {
+        let d = Distribution::of(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(d.mean, 2.5);
+        assert_eq!(d.median, 2.5);
+        assert_eq!((d.min, d.max), (1.0, 4.0));
+        assert!((d.stddev - (5.0f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert_eq!(Distribution::of(&[7.0]).stddev, 0.0);
+        assert_eq!(Distribution::of(&[]), Distribution::default());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let spec = AgentSpec::Random;
+        let params = EvalParams { games: 8, seed: 1 };
+        let played = std::sync::atomic::AtomicUsize::new(0);
+        let results = evaluate(&spec, &params, |_| {
+            played.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        });
+        assert_eq!(played.into_inner(), 8);
+        assert_eq!(
+            results.iter().map(|r| r.seed).collect::<Vec<_>>(),
+            params.game_seeds()
+        );
+        assert_eq!(results, evaluate(&spec, &params, |_| {}));
+
+        let evaluation = Evaluation::new(&spec, results);
+        assert_eq!(evaluation.summary.max_tiles.values().sum::<usize>(), 8);
+        let s = serde_json::to_string(&evaluation).unwrap();
+        assert_eq!(serde_json::from_str::<Evaluation>(&s).unwrap(), evaluation);
+    }
+}
@@ -0,0 +1,639 @@
+//! Packed board representation.
+//!
+//! A board of `width` x `height` cells is stored in a `u128` holding 4 bits per cell, so boards
+//! may have at most 32 cells. Each cell stores the exponent of its tile (0 for empty, 1 for a 2,
+//! 2 for a 4, ...), so the largest representable tile is 2^15. Setting a larger exponent panics,
+//! and two 2^15 tiles don't merge, since the tile they would make can't be stored; reaching them
+//! takes a game far beyond any agent here. Cell `(x, y)` lives in nibble
+//! `x + y * width`, with nibble 0 in the least significant bits, which makes every row a
+//! contiguous bit range. Moves slide each row through lookup tables indexed by the row value
+//! (for rows of up to 4 cells), and vertical moves transpose the board so columns become rows.
+
+use std::{fmt, str::FromStr};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::Move;
+
+pub const MAX_CELLS: usize = 32;
+pub const MAX_EXPONENT: u8 = 15;
+
+/// Rows up to this length are resolved through precomputed tables.
+const MAX_TABLE_LEN: usize = 4;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "BoardRepr", into = "BoardRepr")]
+pub struct Board {
+    width: u8,
+    height: u8,
+    cells: u128,
+}
+
+/// The serialized form of a board: its size and the tile exponents in row-major order.
+#[derive(Serialize, Deserialize)]
+struct BoardRepr {
+    width: u8,
+    height: u8,
+    tiles: Vec<u8>,
+}
+
+impl From<Board> for BoardRepr {
+    fn from(board: Board) -> Self {
+        BoardRepr {
+            width: board.width,
+            height: board.height,
+            tiles: board.tiles(),
+        }
+    }
+}
+
+impl TryFrom<BoardRepr> for Board {
+    type Error = String;
+
+    fn try_from(repr: BoardRepr) -> Result<Self, Self::Error> {
+        check_size(repr.width as usize, repr.height as usize)?;
+        let mut board = Board::empty(repr.width as usize, repr.height as usize);
+        if repr.tiles.len() != board.num_cells() {
+            return Err(format!(
+                "expected {} tiles for a {}x{} board, found {}",
+                board.num_cells(),
+                repr.width,
+                repr.height,
+                repr.tiles.len()
+            ));
+        }
+        if let Some(n) = repr.tiles.iter().find(|n| **n > MAX_EXPONENT) {
+            return Err(format!(
+                "tile exponent {} is larger than {}",
+                n, MAX_EXPONENT
+            ));
+        }
+        board.set_tiles(&repr.tiles);
+        Ok(board)
+    }
+}
+
+/// Writes the board as rows of tile values, one row per line, with `.` for empty cells. Columns
+/// are padded to the widest tile so they line up:
+///
+/// ```text
+/// 2    . .   .
+/// .    4 . 128
+/// . 2048 .   .
+/// .    . .   .
+/// ```
+///
+/// Parsing this with [`Board::from_str`] gives back the same board.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cell = |e: u8| match e {
+            0 => ".".to_string(),
+            e => (1_u32 << e).to_string(),
+        };
+        let widths = (0..self.width())
+            .map(|x| {
+                (0..self.height())
+                    .map(|y| cell(self.get(x, y)).len())
+                    .max()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        for y in 0..self.height() {
+            if y > 0 {
+                writeln!(f)?;
+            }
+            for (x, width) in widths.iter().enumerate() {
+                if x > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{:>width$}", cell(self.get(x, y)), width = width)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the notation written by [`Board`]'s `Display`. Rows are separated by newlines or by
+/// `/`, so a board also fits on one line (`2 . . ./. 4 . 128/. 2048 . ./. . . .`), and tiles
+/// within a row by any whitespace. Blank lines and surrounding whitespace are ignored.
+impl FromStr for Board {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s
+            .split(['\n', '/'])
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                row.split_whitespace()
+                    .map(parse_tile)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        if let Some(row) = rows.iter().find(|row| row.len() != width) {
+            return Err(format!(
+                "rows must all be the same length, found rows of {} and {} tiles",
+                width,
+                row.len()
+            ));
+        }
+        check_size(width, height)?;
+
+        let mut board = Board::empty(width, height);
+        board.set_tiles(&rows.concat());
+        Ok(board)
+    }
+}
+
+/// The exponent of a tile written as `.` or as its face value.
+fn parse_tile(s: &str) -> Result<u8, String> {
+    if s == "." {
+        return Ok(0);
+    }
+    let invalid = || {
+        format!(
+            "'{}' is not a tile, tiles are '.' or powers of two from 2 to {}",
+            s,
+            1 << MAX_EXPONENT
+        )
+    };
+    let value = s.parse::<u32>().map_err(|_| invalid())?;
+    if !value.is_power_of_two() || value < 2 || value.trailing_zeros() > MAX_EXPONENT as u32 {
+        return Err(invalid());
+    }
+    Ok(value.trailing_zeros() as u8)
+}
+
+/// Checks that a board of the given size can be represented.
+pub fn check_size(width: usize, height: usize) -> Result<(), String> {
+    if width < 2 || height < 2 {
+        return Err(format!(
+            "a {}x{} board is too small, both sides must be at least 2",
+            width, height
+        ));
+    }
+    if width * height > MAX_CELLS {
+        return Err(format!(
+            "a {}x{} board has more than {} cells",
+            width, height, MAX_CELLS
+        ));
+    }
+    Ok(())
+}
+
+/// Result of sliding every possible row of one length, indexed by the row value.
+struct RowTables {
+    left: Vec<u16>,
+    right: Vec<u16>,
+    score: Vec<u32>,
+}
+
+impl RowTables {
+    fn new(len: usize) -> Self {
+        let size = 1 << (4 * len);
+        let mut left = vec![0; size];
+        let mut right = vec![0; size];
+        let mut score = vec![0; size];
+        for row in 0..size as u64 {
+            let (slid, gained) = slide_row_left(row, len);
+            left[row as usize] = slid as u16;
+            right[reverse_row(row, len) as usize] = reverse_row(slid, len) as u16;
+            score[row as usize] = gained;
+        }
+        RowTables { left, right, score }
+    }
+}
+
+// indexed by row length
+static TABLES: Lazy<Vec<RowTables>> =
+    Lazy::new(|| (0..=MAX_TABLE_LEN).map(RowTables::new).collect());
+
+/// Slides a single row of `len` cells towards nibble 0, merging equal neighbours once per move.
+/// Returns the new row and the score gained from the merges.
+fn slide_row_left(row: u64, len: usize) -> (u64, u32) {
+    let mut slid = 0;
+    let mut score = 0;
+    let mut j = 0;
+    let mut i = 0;
+    let cell = |i: usize| (row >> (4 * i)) & 0xF;
+    while i < len {
+        if cell(i) == 0 {
+            i += 1;
+            continue;
+        }
+        // find the next non-empty tile to try and merge with
+        let mut k = i + 1;
+        while k < len && cell(k) == 0 {
+            k += 1;
+        }
+        let value = if k < len && cell(k) == cell(i) && cell(i) < MAX_EXPONENT as u64 {
+            score += 1 << (cell(i) + 1);
+            i = k + 1;
+            cell(k) + 1
+        } else {
+            let value = cell(i);
+            i = k;
+            value
+        };
+        slid |= value << (4 * j);
+        j += 1;
+    }
+    (slid, score)
+}
+
+fn reverse_row(row: u64, len: usize) -> u64 {
+    (0..len).fold(0, |acc, i| {
+        acc | (((row >> (4 * i)) & 0xF) << (4 * (len - 1 - i)))
+    })
+}
+
+fn row_mask(len: usize) -> u64 {
+    if len == 16 {
+        u64::MAX
+    } else {
+        (1 << (4 * len)) - 1
+    }
+}
+
+/// Swaps rows and columns of a packed 4x4 board, so that cell `(x, y)` moves to `(y, x)`.
+fn transpose_4x4(board: u64) -> u64 {
+    let a1 = board & 0xF0F0_0F0F_F0F0_0F0F;
+    let a2 = board & 0x0000_F0F0_0000_F0F0;
+    let a3 = board & 0x0F0F_0000_0F0F_0000;
+    let a = a1 | (a2 << 12) | (a3 >> 12);
+    let b1 = a & 0xFF00_FF00_00FF_00FF;
+    let b2 = a & 0x00FF_00FF_0000_0000;
+    let b3 = a & 0x0000_0000_FF00_FF00;
+    b1 | (b2 >> 24) | (b3 << 24)
+}
+
+/// Slides every row of a packed 4x4 board. This is by far the most common size, so it skips the
+/// bookkeeping needed for other sizes.
+fn slide_rows_4x4(board: u64, reverse: bool) -> (u64, u32) {
+    let tables = &TABLES[4];
+    let table = if reverse { &tables.right } else { &tables.left };
+    (0..4).fold((0, 0), |(acc, score), y| {
+        let row = ((board >> (16 * y)) & 0xFFFF) as usize;
+        (
+            acc | ((table[row] as u64) << (16 * y)),
+            score + tables.score[row],
+        )
+    })
+}
+
+fn shift_4x4(board: u64, input: Move) -> (u64, u32) {
+    match input {
+        Move::Left => slide_rows_4x4(board, false),
+        Move::Right => slide_rows_4x4(board, true),
+        Move::Up => {
+            let (t, score) = slide_rows_4x4(transpose_4x4(board), false);
+            (transpose_4x4(t), score)
+        }
+        Move::Down => {
+            let (t, score) = slide_rows_4x4(transpose_4x4(board), true);
+            (transpose_4x4(t), score)
+        }
+    }
+}
+
+impl Board {
+    /// Creates an empty board.
+    ///
+    /// # Panics
+    /// If the size is rejected by [`check_size`].
+    pub fn empty(width: usize, height: usize) -> Self {
+        if let Err(e) = check_size(width, height) {
+            panic!("{}", e);
+        }
+        Board {
+            width: width as u8,
+            height: height as u8,
+            cells: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    /// The packed cells, with 4 bits per cell.
+    pub fn packed(&self) -> u128 {
+        self.cells
+    }
+
+    pub fn get_idx(&self, idx: usize) -> u8 {
+        assert!(idx < self.num_cells(), "cell {} is off the board", idx);
+        self.cell(idx)
+    }
+
+    fn cell(&self, idx: usize) -> u8 {
+        ((self.cells >> (4 * idx)) & 0xF) as u8
+    }
+
+    /// Sets the exponent of a cell.
+    ///
+    /// # Panics
+    /// If the cell is off the board, or `value` is larger than [`MAX_EXPONENT`].
+    pub fn set_idx(&mut self, idx: usize, value: u8) {
+        assert!(idx < self.num_cells(), "cell {} is off the board", idx);
+        assert!(
+            value <= MAX_EXPONENT,
+            "tile exponent {} is larger than {}",
+            value,
+            MAX_EXPONENT
+        );
+        let shift = 4 * idx;
+        self.cells = (self.cells & !(0xF << shift)) | ((value as u128 & 0xF) << shift);
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        assert!(x < self.width(), "column {} is off the board", x);
+        self.get_idx(x + y * self.width())
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        assert!(x < self.width(), "column {} is off the board", x);
+        self.set_idx(x + y * self.width(), value)
+    }
+
+    /// The tile exponents in row-major order.
+    pub fn tiles(&self) -> Vec<u8> {
+        (0..self.num_cells()).map(|i| self.get_idx(i)).collect()
+    }
+
+    /// Replaces every cell from a row-major list of exponents.
+    ///
+    /// # Panics
+    /// If `tiles` does not hold exactly one value per cell, or holds an exponent larger than
+    /// [`MAX_EXPONENT`].
+    pub fn set_tiles(&mut self, tiles: &[u8]) {
+        assert_eq!(
+            tiles.len(),
+            self.num_cells(),
+            "wrong number of tiles for board"
+        );
+        self.cells = 0;
+        for (i, n) in tiles.iter().enumerate() {
+            self.set_idx(i, *n);
+        }
+    }
+
+    /// Number of empty cells on the board.
+    pub fn count_empty(&self) -> usize {
+        // fold each nibble down to a single bit that is set when the nibble is non-zero
+        let mut x = self.cells | (self.cells >> 2);
+        x |= x >> 1;
+        x &= 0x1111_1111_1111_1111_1111_1111_1111_1111;
+        self.num_cells() - x.count_ones() as usize
+    }
+
+    /// Indexes of the empty cells.
+    pub fn empty_cells(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_cells()).filter(|i| self.cell(*i) == 0)
+    }
+
+    /// Exponent of the largest tile.
+    pub fn max_exponent(&self) -> u8 {
+        (0..self.num_cells())
+            .map(|i| self.cell(i))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Index of the `n`th empty cell, counting in row-major order.
+    pub fn nth_empty(&self, mut n: usize) -> Option<usize> {
+        let mut cells = self.cells;
+        for i in 0..self.num_cells() {
+            if cells & 0xF == 0 {
+                if n == 0 {
+                    return Some(i);
+                }
+                n -= 1;
+            }
+            cells >>= 4;
+        }
+        None
+    }
+
+    /// Swaps rows and columns, so that cell `(x, y)` moves to `(y, x)`.
+    pub fn transpose(&self) -> Board {
+        if self.width == 4 && self.height == 4 {
+            return Board {
+                cells: transpose_4x4(self.cells as u64) as u128,
+                ..*self
+            };
+        }
+        let mut t = Board {
+            width: self.height,
+            height: self.width,
+            cells: 0,
+        };
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                t.set(y, x, self.get(x, y));
+            }
+        }
+        t
+    }
+
+    /// Slides every row towards the start (`reverse == false`) or end of the row, returning the
+    /// new board and the score gained. Merges happen pairwise within runs of equal tiles, so a
+    /// row scores the same in either direction.
+    fn slide_rows(&self, reverse: bool) -> (Board, u32) {
+        let len = self.width();
+        let mask = row_mask(len);
+        let tables = TABLES.get(len);
+        let mut cells = 0;
+        let mut score = 0;
+        for y in 0..self.height() {
+            let shift = 4 * len * y;
+            let row = (self.cells >> shift) as u64 & mask;
+            let (slid, gained) = match tables {
+                Some(tables) => {
+                    let slid = if reverse {
+                        tables.right[row as usize]
+                    } else {
+                        tables.left[row as usize]
+                    };
+                    (slid as u64, tables.score[row as usize])
+                }
+                None if reverse => {
+                    let (slid, gained) = slide_row_left(reverse_row(row, len), len);
+                    (reverse_row(slid, len), gained)
+                }
+                None => slide_row_left(row, len),
+            };
+            cells |= (slid as u128) << shift;
+            score += gained;
+        }
+        (Board { cells, ..*self }, score)
+    }
+
+    /// Applies a move, returning the new board and the score gained. No tile is spawned.
+    pub fn shift(&self, input: Move) -> (Board, u32) {
+        if self.width == 4 && self.height == 4 {
+            let (cells, score) = shift_4x4(self.cells as u64, input);
+            return (
+                Board {
+                    cells: cells as u128,
+                    ..*self
+                },
+                score,
+            );
+        }
+        match input {
+            Move::Left => self.slide_rows(false),
+            Move::Right => self.slide_rows(true),
+            Move::Up => {
+                let (t, score) = self.transpose().slide_rows(false);
+                (t.transpose(), score)
+            }
+            Move::Down => {
+                let (t, score) = self.transpose().slide_rows(true);
+                (t.transpose(), score)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(cells: &[u64]) -> u64 {
+        cells
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, c)| acc | (c << (4 * i)))
+    }
+
+    #[test]
+    fn test_slide_row() {
+        assert_eq!(slide_row_left(row(&[1, 1, 2, 2]), 4), (row(&[2, 3]), 12));
+        assert_eq!(slide_row_left(row(&[1, 2, 2, 2]), 4), (row(&[1, 3, 2]), 8));
+        assert_eq!(slide_row_left(row(&[1, 1, 1, 1]), 4), (row(&[2, 2]), 8));
+        assert_eq!(slide_row_left(row(&[1, 2, 2, 6]), 4), (row(&[1, 3, 6]), 8));
+        assert_eq!(slide_row_left(row(&[0, 1, 0, 1]), 4), (row(&[2]), 4));
+        assert_eq!(
+            slide_row_left(row(&[1, 0, 1, 3, 3, 3]), 6),
+            (row(&[2, 4, 3]), 20)
+        );
+    }
+
+    #[test]
+    fn test_largest_tiles_dont_merge() {
+        // a 2^16 tile can't be stored, so the largest tiles stay side by side
+        assert_eq!(slide_row_left(row(&[15, 15]), 4), (row(&[15, 15]), 0));
+        let mut board = Board::empty(2, 2);
+        board.set_tiles(&[15, 15, 14, 14]);
+        let (left, score) = board.shift(Move::Left);
+        assert_eq!(left.tiles(), [15, 15, 15, 0]);
+        assert_eq!(score, 1 << 15);
+    }
+
+    #[test]
+    #[should_panic(expected = "larger than 15")]
+    fn test_exponent_too_large() {
+        Board::empty(4, 4).set_idx(0, 16);
+    }
+
+    #[test]
+    fn test_notation() {
+        let mut board = Board::empty(4, 4);
+        board.set_tiles(&[1, 0, 0, 0, 0, 2, 0, 7, 0, 11, 0, 0, 0, 0, 0, 0]);
+        let s = board.to_string();
+        assert_eq!(s, "2    . .   .\n.    4 . 128\n. 2048 .   .\n.    . .   .");
+        assert_eq!(s.parse::<Board>().unwrap(), board);
+        assert_eq!(
+            "2 . . ./. 4 . 128/. 2048 . ./. . . ."
+                .parse::<Board>()
+                .unwrap(),
+            board
+        );
+
+        let mut board = Board::empty(3, 2);
+        board.set_tiles(&[15, 0, 1, 0, 3, 0]);
+        assert_eq!(board.to_string().parse::<Board>().unwrap(), board);
+
+        assert!("2 . ./. 4".parse::<Board>().is_err());
+        assert!("2 3/. .".parse::<Board>().is_err());
+        assert!("2 65536/. .".parse::<Board>().is_err());
+        assert!("2".parse::<Board>().is_err());
+    }
+
+    #[test]
+    fn test_right_table() {
+        let r = row(&[1, 1, 0, 2]);
+        assert_eq!(TABLES[4].right[r as usize] as u64, row(&[0, 0, 2, 2]));
+        assert_eq!(TABLES[4].score[r as usize], 4);
+        let r = row(&[1, 1, 1]);
+        assert_eq!(TABLES[3].right[r as usize] as u64, row(&[0, 1, 2]));
+    }
+
+    #[test]
+    fn test_transpose() {
+        for (w, h) in [(4, 4), (3, 3), (2, 5), (5, 6)] {
+            let mut board = Board::empty(w, h);
+            for i in 0..board.num_cells() {
+                board.set_idx(i, (i % 16) as u8);
+            }
+            let t = board.transpose();
+            assert_eq!((t.width(), t.height()), (h, w));
+            for x in 0..w {
+                for y in 0..h {
+                    assert_eq!(t.get(y, x), board.get(x, y));
+                }
+            }
+            assert_eq!(t.transpose(), board);
+        }
+    }
+
+    #[test]
+    fn test_shift_rectangular() {
+        let mut board = Board::empty(5, 3);
+        board.set_tiles(&[1, 1, 0, 2, 2, 0, 0, 0, 0, 3, 4, 0, 0, 0, 4]);
+
+        let (left, score) = board.shift(Move::Left);
+        assert_eq!(left.tiles(), [2, 3, 0, 0, 0, 3, 0, 0, 0, 0, 5, 0, 0, 0, 0]);
+        assert_eq!(score, 4 + 8 + 32);
+
+        let (down, score) = board.shift(Move::Down);
+        assert_eq!(down.tiles(), [0, 0, 0, 0, 2, 1, 0, 0, 0, 3, 4, 1, 0, 2, 4]);
+        assert_eq!(score, 0);
+    }
+
+    #[test]
+    fn test_count_empty() {
+        let mut board = Board::empty(4, 4);
+        assert_eq!(board.count_empty(), 16);
+        board.set_idx(3, 1);
+        board.set_idx(15, 15);
+        assert_eq!(board.count_empty(), 14);
+
+        let mut board = Board::empty(8, 4);
+        board.set_idx(31, 2);
+        assert_eq!(board.count_empty(), 31);
+    }
+
+    #[test]
+    fn test_serde() {
+        let mut board = Board::empty(3, 2);
+        board.set_tiles(&[1, 0, 2, 0, 15, 3]);
+        let s = ron::to_string(&board).unwrap();
+        assert_eq!(ron::from_str::<Board>(&s).unwrap(), board);
+        assert!(ron::from_str::<Board>("(width: 3, height: 2, tiles: [1, 2])").is_err());
+        assert!(ron::from_str::<Board>("(width: 9, height: 9, tiles: [])").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+
+use super::{board::Board, outcome::MoveOutcome, outcome::Spawn, Game, Move};
+
+/// One recorded move, with the tiles that spawned after it.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct Step {
+    direction: Move,
+    spawns: Vec<Spawn>,
+    score_delta: usize,
+    before: Game,
+    after: Game,
+}
+
+impl Step {
+    fn new(direction: Move, spawns: Vec<Spawn>, before: Game, after: Game) -> Self {
+        Step {
+            direction,
+            spawns,
+            score_delta: after.score - before.score,
+            before,
+            after,
+        }
+    }
+
+    pub fn direction(&self) -> Move {
+        self.direction
+    }
+
+    pub fn spawns(&self) -> &[Spawn] {
+        &self.spawns
+    }
+
+    pub fn score_delta(&self) -> usize {
+        self.score_delta
+    }
+
+    pub fn board_before(&self) -> &Board {
+        &self.before.board
+    }
+
+    pub fn board_after(&self) -> &Board {
+        &self.after.board
+    }
+}
+
+/// The moves played in a game, split at the current position into moves that can be undone and
+/// moves that were undone and can be redone.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+}
+
+impl History {
+    /// Moves leading up to the current position, oldest first.
+    pub fn past(&self) -> &[Step] {
+        &self.undo
+    }
+
+    /// Undone moves, with the next one to redo last.
+    pub fn future(&self) -> &[Step] {
+        &self.redo
+    }
+
+    fn push(&mut self, step: Step) {
+        self.undo.push(step);
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) -> Option<Game> {
+        let step = self.undo.pop()?;
+        let before = step.before;
+        self.redo.push(step);
+        Some(before)
+    }
+
+    fn redo(&mut self) -> Option<Game> {
+        let step = self.redo.pop()?;
+        let after = step.after;
+        self.undo.push(step);
+        Some(after)
+    }
+}
+
+/// A game that records its moves so they can be undone. The history is kept out of [`Game`]
+/// itself, so that the copies search agents make stay small and compare by position alone.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct RecordedGame {
+    game: Game,
+    history: History,
+}
+
+impl RecordedGame {
+    /// Starts recording from `game`. Moves played before this can't be undone.
+    pub fn new(game: Game) -> Self {
+        RecordedGame {
+            game,
+            history: History::default(),
+        }
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Plays a move like [`Game::make_move`], recording it if it changed the board.
+    pub fn make_move(&mut self, input: Move) -> MoveOutcome {
+        let before = self.game;
+        let outcome = self.game.make_move(input);
+        if outcome.moved() {
+            self.history
+                .push(Step::new(input, outcome.spawns(), before, self.game));
+        }
+        outcome
+    }
+
+    /// Takes back the last recorded move, restoring the board, score and spawn generator.
+    /// Returns whether there was a move to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(game) = self.history.undo() else {
+            return false;
+        };
+        self.game = game;
+        true
+    }
+
+    /// Replays the last undone move. Returns whether there was a move to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(game) = self.history.redo() else {
+            return false;
+        };
+        self.game = game;
+        true
+    }
+
+    /// Undoes every recorded move, going back to where recording started.
+    pub fn rewind(&mut self) {
+        while self.undo() {}
+    }
+}
@@ -0,0 +1,745 @@
+use std::{fmt, str::FromStr};
+
+use board::Board;
+use enum_map::Enum;
+use outcome::MoveOutcome;
+use rng::Rng;
+use rules::Rules;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+pub mod board;
+pub mod history;
+pub mod outcome;
+pub mod replay;
+pub mod rng;
+pub mod rules;
+pub(crate) mod save;
+pub mod symmetry;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Game {
+    board: Board,
+    score: usize,
+    num_moves: usize,
+    rng: Rng,
+    #[serde(default)]
+    rules: Rules,
+}
+
+/// Writes the board in the notation described on [`Board`]'s `Display`.
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.board.fmt(f)
+    }
+}
+
+/// Reads a board in the notation described on [`Board`]'s `FromStr` into a fresh game with the
+/// default rules, no score and a random seed. Only the board is part of the notation, so
+/// `game.to_string().parse::<Game>()` gives back the same board, but not the same score or
+/// spawns.
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let board = s.parse::<Board>()?;
+        let mut game = Game::empty_sized(board.width(), board.height());
+        game.board = board;
+        Ok(game)
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new_sized(4, 4)
+    }
+}
+
+#[derive(
+    Enum, EnumIter, Debug, PartialEq, Eq, Hash, Clone, Copy, Display, Serialize, Deserialize,
+)]
+pub enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Game::default()
+    }
+
+    pub fn empty() -> Self {
+        Game::empty_sized(4, 4)
+    }
+
+    /// Creates an empty game on a `width` x `height` board.
+    ///
+    /// # Panics
+    /// If the board size is rejected by [`board::check_size`].
+    pub fn empty_sized(width: usize, height: usize) -> Self {
+        Game {
+            board: Board::empty(width, height),
+            score: 0,
+            num_moves: 0,
+            rng: Rng::from_entropy(),
+            rules: Rules::default(),
+        }
+    }
+
+    /// Creates a game whose tile spawns are entirely determined by `seed` and the moves played.
+    pub fn new_seeded(seed: u64) -> Self {
+        Game::new_seeded_sized(seed, 4, 4)
+    }
+
+    pub fn new_sized(width: usize, height: usize) -> Self {
+        Game::new_seeded_sized(fastrand::u64(..), width, height)
+    }
+
+    pub fn new_seeded_sized(seed: u64, width: usize, height: usize) -> Self {
+        Game::new_with_rules(seed, width, height, Rules::default())
+    }
+
+    /// Creates a game on a `width` x `height` board played under `rules`, with the starting tiles
+    /// spawned from `seed`.
+    pub fn new_with_rules(seed: u64, width: usize, height: usize, rules: Rules) -> Self {
+        let mut game = Game::empty_sized(width, height);
+        game.rules = rules;
+        game.reseed(seed);
+        for _ in 0..rules.initial_tiles() {
+            game.generate_tile();
+        }
+        game
+    }
+
+    /// A 4x4 game with the given tile exponents, in row-major order.
+    ///
+    /// # Panics
+    /// If an exponent is larger than [`board::MAX_EXPONENT`].
+    pub fn new_from(state: [u8; 16]) -> Self {
+        let mut game = Game::default();
+        game.set_state(&state);
+        game
+    }
+
+    /// Whether the move would change the board. No move can be played once the game is won, if
+    /// the rules don't allow playing on.
+    pub fn can_move(&self, input: Move) -> bool {
+        !self.stopped_by_win() && self.board.shift(input).0 != self.board
+    }
+
+    pub fn available_moves(&self) -> impl Iterator<Item = Move> {
+        let board = self.board;
+        let stopped = self.stopped_by_win();
+        Move::iter().filter(move |m| !stopped && board.shift(*m).0 != board)
+    }
+
+    /// Whether the game was won and the rules don't allow playing on.
+    fn stopped_by_win(&self) -> bool {
+        !self.rules.continue_after_win() && self.won()
+    }
+
+    /// Plays a move and spawns a new tile. If the move doesn't change the board, or the game was
+    /// won and the rules don't allow playing on, nothing happens and the outcome reports that it
+    /// didn't move.
+    pub fn make_move(&mut self, input: Move) -> MoveOutcome {
+        let before = self.board;
+        let score_before = self.score;
+        if !self.stopped_by_win() {
+            self.shift(input);
+        }
+        let afterstate = self.board;
+        if before != afterstate {
+            for _ in 0..self.rules.spawns_per_move() {
+                self.generate_tile();
+            }
+            self.num_moves += 1;
+        }
+        MoveOutcome::new(
+            input,
+            before,
+            afterstate,
+            self.board,
+            self.score - score_before,
+        )
+    }
+
+    /// The position right after sliding the tiles for a move, before any tile spawns, or `None`
+    /// if the move doesn't change the board. Together with [`Game::spawn_outcomes`] this splits
+    /// [`Game::make_move`] into its deterministic and random halves, which is what search agents
+    /// need.
+    pub fn afterstate(&self, input: Move) -> Option<Game> {
+        if self.stopped_by_win() {
+            return None;
+        }
+        let (board, score) = self.board.shift(input);
+        if board == self.board {
+            return None;
+        }
+        Some(Game {
+            board,
+            score: self.score + score as usize,
+            num_moves: self.num_moves + 1,
+            rng: self.rng,
+            rules: self.rules,
+        })
+    }
+
+    /// Every way tiles can spawn on this position under the game's rules, with the probability
+    /// of each. The probabilities sum to 1. When several tiles spawn per move, the same board can
+    /// be reached in more than one order and is then listed once per order. A full board yields
+    /// itself, unchanged, with probability 1.
+    pub fn spawn_outcomes(&self) -> impl Iterator<Item = (Game, f32)> {
+        let mut outcomes = vec![(*self, 1.0)];
+        for _ in 0..self.rules.spawns_per_move() {
+            outcomes = outcomes
+                .iter()
+                .flat_map(|(game, p)| game.single_spawn_outcomes(*p))
+                .collect();
+        }
+        outcomes.into_iter()
+    }
+
+    fn single_spawn_outcomes(&self, p: f32) -> Vec<(Game, f32)> {
+        let empty = self.count_empty();
+        if empty == 0 {
+            return vec![(*self, p)];
+        }
+        self.board
+            .empty_cells()
+            .flat_map(|idx| {
+                self.rules.spawn_probabilities().map(move |(n, sp)| {
+                    let mut game = *self;
+                    game.board.set_idx(idx, n);
+                    (game, p * sp / empty as f32)
+                })
+            })
+            .collect()
+    }
+
+    pub fn width(&self) -> usize {
+        self.board.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.board.height()
+    }
+
+    pub fn get_tile(&self, x: u8, y: u8) -> u8 {
+        self.board.get(x as usize, y as usize)
+    }
+
+    /// Sets the exponent of the tile at `(x, y)`.
+    ///
+    /// # Panics
+    /// If the tile is off the board, or `value` is larger than [`board::MAX_EXPONENT`].
+    pub fn set_tile(&mut self, x: u8, y: u8, value: u8) {
+        self.board.set(x as usize, y as usize, value);
+    }
+
+    pub fn get_table(&self) -> Vec<Vec<u32>> {
+        let pows = self
+            .get_state()
+            .iter()
+            .map(|n| 2_u32.pow(*n as u32))
+            .collect::<Vec<_>>();
+        pows.chunks(self.width()).map(|s| s.into()).collect()
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Changes the rules mid-game. They apply from the next spawn on.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// Value of the largest tile on the board, or 0 on an empty board.
+    pub fn max_tile(&self) -> u32 {
+        match self.board.max_exponent() {
+            0 => 0,
+            e => 1 << e,
+        }
+    }
+
+    /// Whether the target tile of the rules has been reached.
+    pub fn won(&self) -> bool {
+        self.board.max_exponent() >= self.rules.win_exponent()
+    }
+
+    /// Whether no more moves can be played, either because none are possible or because the game
+    /// was won and the rules don't allow playing on.
+    pub fn game_over(&self) -> bool {
+        if self.stopped_by_win() {
+            return true;
+        }
+
+        // game is not over if any tile is empty
+        if self.count_empty() > 0 {
+            return false;
+        }
+
+        // on a full board, a horizontal move is possible exactly when some row has equal
+        // neighbours, and a vertical move when some column does
+        !self.can_move(Move::Left) && !self.can_move(Move::Up)
+    }
+
+    pub fn count_empty(&self) -> usize {
+        self.board.count_empty()
+    }
+
+    pub fn get_num_moves(&self) -> &usize {
+        &self.num_moves
+    }
+
+    pub fn get_score(&self) -> &usize {
+        &self.score
+    }
+
+    /// The tile exponents in row-major order.
+    pub fn get_state(&self) -> Vec<u8> {
+        self.board.tiles()
+    }
+
+    /// Replaces the tile exponents, given in row-major order.
+    ///
+    /// # Panics
+    /// If `s` does not hold exactly one value per cell of the board, or holds an exponent larger
+    /// than [`board::MAX_EXPONENT`].
+    pub fn set_state(&mut self, s: &[u8]) {
+        self.board.set_tiles(s);
+    }
+
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    /// Replaces the generator used for tile spawns. Simulations use this to make clones of a game
+    /// diverge from each other.
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn shift(&mut self, input: Move) {
+        let (board, score) = self.board.shift(input);
+        self.board = board;
+        self.score += score as usize;
+    }
+
+    fn generate_tile(&mut self) {
+        let empty = self.count_empty();
+        if empty == 0 {
+            return;
+        }
+
+        let nth = self.rng.usize(0..empty);
+        let c_idx = self.board.nth_empty(nth).unwrap();
+        let n = self.rules.sample_spawn(self.rng.f32());
+        self.board.set_idx(c_idx, n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::history::RecordedGame;
+    use super::outcome::{Merge, Slide};
+    use super::rules::SpawnWeight;
+    use super::*;
+    #[test]
+    fn set_state_and_tile() {
+        let mut game = Game::empty();
+        assert_eq!(game.get_tile(0, 0), 0);
+
+        game.set_state(&[
+            0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xa, 0xb, 0xc, 0xd, 0xe, 0xf, 0x0,
+        ]);
+
+        assert_eq!(game.get_tile(0, 0), 0x1);
+        assert_eq!(game.get_tile(1, 0), 0x2);
+        assert_eq!(game.get_tile(2, 0), 0x3);
+        assert_eq!(game.get_tile(3, 0), 0x4);
+        assert_eq!(game.get_tile(0, 1), 0x5);
+        assert_eq!(game.get_tile(3, 3), 0x0);
+
+        game.set_tile(0, 1, 0x00);
+        assert_eq!(game.get_tile(0, 1), 0x00);
+    }
+
+    #[test]
+    #[should_panic(expected = "tile exponent 16 is larger than 15")]
+    fn test_tiles_beyond_32768_are_rejected() {
+        // tiles are stored in 4 bits, so a 65536 tile can't be kept
+        let mut state = [0; 16];
+        state[0] = 16;
+        Game::new_from(state);
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut game = Game::empty();
+
+        game.set_tile(0, 0, 1);
+        game.set_tile(1, 0, 1);
+
+        game.shift(Move::Right);
+
+        assert_eq!(game.get_tile(0, 0), 0);
+        assert_eq!(game.get_tile(1, 0), 0);
+        assert_eq!(game.get_tile(2, 0), 0);
+        assert_eq!(game.get_tile(3, 0), 2);
+
+        game.shift(Move::Left);
+
+        assert_eq!(game.get_tile(0, 0), 2);
+
+        game.shift(Move::Down);
+
+        assert_eq!(game.get_tile(0, 3), 2);
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let mut game = Game::empty();
+        let merge = |game: &mut Game, row: [u8; 4]| {
+            let mut state = [0; 16];
+            state[..4].copy_from_slice(&row);
+            game.set_state(&state);
+            game.shift(Move::Left);
+            game.get_state()[..4].to_vec()
+        };
+        assert_eq!(merge(&mut game, [1, 1, 2, 2]), [2, 3, 0, 0]);
+        assert_eq!(game.get_score(), &12);
+        assert_eq!(merge(&mut game, [1, 2, 2, 2]), [1, 3, 2, 0]);
+        assert_eq!(game.get_score(), &(12 + 8));
+        assert_eq!(merge(&mut game, [1, 1, 1, 1]), [2, 2, 0, 0]);
+        assert_eq!(game.get_score(), &(12 + 8 + 8));
+        assert_eq!(merge(&mut game, [1, 2, 2, 6]), [1, 3, 6, 0]);
+    }
+
+    #[test]
+    fn test_condensed_getters() {
+        let mut game = Game::empty();
+
+        game.set_tile(0, 0, 1);
+        game.set_tile(1, 0, 1);
+        game.set_tile(0, 3, 2);
+
+        let rows = game.get_state();
+        let rows = rows.chunks(4).collect::<Vec<_>>();
+        let cols = game.get_board().transpose().tiles();
+        let cols = cols.chunks(4).collect::<Vec<_>>();
+
+        assert_eq!(rows[0], [1, 1, 0, 0]);
+        assert_eq!(rows[3], [2, 0, 0, 0]);
+        assert_eq!(cols[0], [1, 0, 0, 2]);
+        assert_eq!(cols[1], [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_shift_columns() {
+        let mut game = Game::empty();
+
+        game.set_tile(0, 0, 1);
+        game.set_tile(1, 0, 1);
+        game.set_tile(0, 3, 2);
+
+        game.shift(Move::Up);
+
+        assert_eq!(game.get_tile(0, 0), 1);
+        assert_eq!(game.get_tile(0, 1), 2);
+        assert_eq!(game.get_tile(1, 0), 1);
+
+        game.shift(Move::Down);
+
+        assert_eq!(game.get_tile(0, 2), 1);
+        assert_eq!(game.get_tile(0, 3), 2);
+        assert_eq!(game.get_tile(1, 3), 1);
+    }
+
+    #[test]
+    fn test_seeded_games_are_reproducible() {
+        let mut a = Game::new_seeded(7);
+        let mut b = Game::new_seeded(7);
+        assert_eq!(a, b);
+
+        // seeding one game must not affect another
+        let _ = Game::new_seeded(8);
+        for m in [Move::Left, Move::Up, Move::Right, Move::Down].repeat(10) {
+            a.make_move(m);
+            b.make_move(m);
+        }
+        assert_eq!(a, b);
+
+        // the generator state travels with the game
+        let mut restored: Game = ron::from_str(&ron::to_string(&a).unwrap()).unwrap();
+        assert_eq!(restored, a);
+        restored.make_move(Move::Left);
+        a.make_move(Move::Left);
+        assert_eq!(restored, a);
+    }
+
+    #[test]
+    fn test_notation() {
+        let game = "2 2 . .\n. 4 . .\n. . 8 .\n. . . 2048"
+            .parse::<Game>()
+            .unwrap();
+        assert_eq!(
+            game.get_state(),
+            [1, 1, 0, 0, 0, 2, 0, 0, 0, 0, 3, 0, 0, 0, 0, 11]
+        );
+        assert_eq!(*game.get_score(), 0);
+        assert_eq!(
+            game.to_string().parse::<Game>().unwrap().get_board(),
+            game.get_board()
+        );
+
+        let game = ". 2 ./. . .".parse::<Game>().unwrap();
+        assert_eq!((game.width(), game.height()), (3, 2));
+        assert!("2 2/2".parse::<Game>().is_err());
+    }
+
+    #[test]
+    fn test_move_outcome() {
+        let mut game = Game::empty();
+        game.reseed(0);
+        game.set_state(&[1, 1, 0, 2, 0, 3, 0, 3, 0, 0, 0, 0, 4, 0, 0, 0]);
+
+        let outcome = game.make_move(Move::Left);
+        assert!(outcome.moved());
+        assert_eq!(outcome.direction(), Move::Left);
+        assert_eq!(outcome.score_delta(), 4 + 16);
+        assert_eq!(
+            outcome.afterstate().tiles(),
+            [2, 2, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0]
+        );
+
+        let spawn = outcome.spawn().unwrap();
+        assert_eq!(outcome.afterstate().get(spawn.at.0, spawn.at.1), 0);
+        assert_eq!(
+            game.get_tile(spawn.at.0 as u8, spawn.at.1 as u8),
+            spawn.value
+        );
+
+        assert_eq!(
+            outcome.merges(),
+            [
+                Merge {
+                    at: (0, 0),
+                    value: 2
+                },
+                Merge {
+                    at: (0, 1),
+                    value: 4
+                }
+            ]
+        );
+        let slides = outcome.slides();
+        assert_eq!(slides.len(), 6);
+        assert!(slides.contains(&Slide {
+            from: (3, 0),
+            to: (1, 0),
+            value: 2,
+            merged: false
+        }));
+        assert!(slides.contains(&Slide {
+            from: (3, 1),
+            to: (0, 1),
+            value: 3,
+            merged: true
+        }));
+        assert!(slides.contains(&Slide {
+            from: (0, 3),
+            to: (0, 3),
+            value: 4,
+            merged: false
+        }));
+
+        game.set_state(&[1, 2, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0]);
+        let before = game;
+        let outcome = game.make_move(Move::Left);
+        assert!(!outcome.moved());
+        assert_eq!(outcome.spawn(), None);
+        assert_eq!(game, before);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let start = Game::new_seeded(3);
+        let mut game = RecordedGame::new(start);
+
+        let mut states = vec![];
+        for m in [Move::Left, Move::Up, Move::Right, Move::Down].repeat(5) {
+            if game.make_move(m).moved() {
+                states.push(*game.game());
+            }
+        }
+        let end = game.clone();
+        assert_eq!(game.history().past().len(), states.len());
+
+        // step back through every position
+        for state in states.iter().rev().skip(1) {
+            assert!(game.undo());
+            assert_eq!(game.game(), state);
+        }
+        assert!(game.undo());
+        assert!(!game.undo());
+        assert_eq!(game.game(), &start);
+
+        // and forward again, spawning the same tiles
+        while game.redo() {}
+        assert_eq!(game, end);
+
+        // playing after an undo drops the undone moves
+        game.rewind();
+        game.make_move(game.game().available_moves().next().unwrap());
+        assert!(game.history().future().is_empty());
+        assert!(!game.redo());
+
+        let restored: RecordedGame = ron::from_str(&ron::to_string(&game).unwrap()).unwrap();
+        assert_eq!(restored, game);
+    }
+
+    #[test]
+    fn test_afterstate_and_spawns() {
+        let mut game = Game::empty();
+        game.set_state(&[1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert!(game.afterstate(Move::Up).is_none());
+        let after = game.afterstate(Move::Right).unwrap();
+        assert_eq!(after.get_tile(3, 0), 2);
+        assert_eq!(after.count_empty(), 15);
+        assert_eq!(after.get_score(), &4);
+        assert_eq!(after.get_num_moves(), &1);
+
+        let outcomes = after.spawn_outcomes().collect::<Vec<_>>();
+        assert_eq!(outcomes.len(), 30);
+        let total: f32 = outcomes.iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+        assert!(outcomes.iter().all(|(g, _)| g.count_empty() == 14));
+        let fours: f32 = outcomes
+            .iter()
+            .filter(|(g, _)| g.get_tile(0, 0) == 2)
+            .map(|(_, p)| p)
+            .sum();
+        assert!((fours - 0.1 / 15.0).abs() < 1e-6);
+
+        // playing the move lands on one of the outcomes
+        game.make_move(Move::Right);
+        assert!(outcomes
+            .iter()
+            .any(|(g, _)| g.get_board() == game.get_board()));
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = Rules::new(&[SpawnWeight { tile: 4, weight: 1 }], 2, 3, 16, false).unwrap();
+        let mut game = Game::new_with_rules(5, 4, 4, rules);
+        assert_eq!(game.count_empty(), 13);
+        assert!(game.get_state().iter().all(|n| *n == 0 || *n == 2));
+
+        let m = game.available_moves().next().unwrap();
+        let after = game.afterstate(m).unwrap();
+        let outcome = game.make_move(m);
+        assert_eq!(outcome.spawns().len(), 2);
+        assert_eq!(game.count_empty(), after.count_empty() - 2);
+
+        // two spawns of a single kind of tile, in either order
+        let empty = after.count_empty() as f32;
+        let outcomes = after.spawn_outcomes().collect::<Vec<_>>();
+        assert_eq!(outcomes.len() as f32, empty * (empty - 1.0));
+        let total: f32 = outcomes.iter().map(|(_, p)| p).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        let mut game = Game::empty();
+        game.set_rules(rules);
+        game.set_state(&[3, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(!game.won());
+        game.make_move(Move::Left);
+        assert!(game.won());
+        assert!(game.game_over());
+        assert_eq!(game.max_tile(), 16);
+
+        // the rules don't allow playing on, so the won game takes no more moves
+        let won = game;
+        assert!(!game.can_move(Move::Right));
+        assert_eq!(game.available_moves().count(), 0);
+        assert_eq!(game.afterstate(Move::Right), None);
+        game.make_move(Move::Right);
+        assert_eq!(game, won);
+
+        game.set_rules(Rules::new(&[SpawnWeight { tile: 4, weight: 1 }], 2, 3, 16, true).unwrap());
+        assert!(!game.game_over());
+        game.make_move(Move::Right);
+        assert_eq!(*game.get_num_moves(), *won.get_num_moves() + 1);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut game = Game::new_seeded_sized(11, 5, 3);
+        for m in [Move::Left, Move::Up, Move::Right, Move::Down].repeat(3) {
+            game.make_move(m);
+        }
+
+        let path = std::env::temp_dir().join(format!("ai-2048-test-{}.ron", std::process::id()));
+        game.save(&path).unwrap();
+        let mut loaded = Game::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, game);
+
+        // the loaded game spawns the same tiles as the original
+        loaded.make_move(Move::Left);
+        game.make_move(Move::Left);
+        assert_eq!(loaded, game);
+        assert!(Game::load(&path).is_err());
+
+        // a recorded game keeps the moves it can undo
+        let mut recorded = RecordedGame::new(game);
+        recorded.make_move(Move::Up);
+        recorded.save(&path).unwrap();
+        let mut loaded = RecordedGame::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, recorded);
+        assert!(loaded.undo());
+        assert_eq!(loaded.game(), &game);
+    }
+
+    #[test]
+    fn test_sized_game() {
+        let mut game = Game::new_seeded_sized(3, 3, 5);
+        assert_eq!((game.width(), game.height()), (3, 5));
+        assert_eq!(game.count_empty(), 13);
+        assert_eq!(game.get_table().len(), 5);
+
+        while let Some(m) = game.available_moves().next() {
+            game.make_move(m);
+        }
+        assert!(game.game_over());
+        assert_eq!(game.count_empty(), 0);
+    }
+
+    #[test]
+    fn test_game_over() {
+        let mut game = Game::empty();
+        game.set_state(&[1, 2, 1, 2, 2, 1, 2, 1, 1, 2, 1, 2, 2, 1, 2, 1]);
+        assert!(game.game_over());
+        assert_eq!(game.available_moves().count(), 0);
+
+        game.set_tile(3, 3, 0);
+        assert!(!game.game_over());
+        assert!(game.can_move(Move::Right));
+        assert!(!game.can_move(Move::Left));
+        assert!(game.can_move(Move::Down));
+        assert!(!game.can_move(Move::Up));
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use super::{board::Board, Move};
+
+/// A single tile moving from one cell to another during a move. Tiles that did not move are
+/// reported too, with `from == to`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Slide {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    /// Exponent of the tile before the move.
+    pub value: u8,
+    /// Whether the tile ended up merged into another one at `to`.
+    pub merged: bool,
+}
+
+/// Two tiles combining into one.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Merge {
+    pub at: (usize, usize),
+    /// Exponent of the merged tile.
+    pub value: u8,
+}
+
+/// A tile placed on the board after a move.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Spawn {
+    pub at: (usize, usize),
+    pub value: u8,
+}
+
+/// Everything that happened during a call to [`Game::make_move`](super::Game::make_move).
+///
+/// Only the boards are stored, so producing an outcome costs nothing on the hot path; slide paths
+/// and merges are worked out when asked for.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct MoveOutcome {
+    direction: Move,
+    before: Board,
+    afterstate: Board,
+    after: Board,
+    score_delta: usize,
+}
+
+impl MoveOutcome {
+    pub(super) fn new(
+        direction: Move,
+        before: Board,
+        afterstate: Board,
+        after: Board,
+        score_delta: usize,
+    ) -> Self {
+        MoveOutcome {
+            direction,
+            before,
+            afterstate,
+            after,
+            score_delta,
+        }
+    }
+
+    pub fn direction(&self) -> Move {
+        self.direction
+    }
+
+    /// Whether the move changed the board. Moves that don't are not played, and spawn nothing.
+    pub fn moved(&self) -> bool {
+        self.before != self.afterstate
+    }
+
+    /// Score gained by the merges of this move.
+    pub fn score_delta(&self) -> usize {
+        self.score_delta
+    }
+
+    pub fn board_before(&self) -> &Board {
+        &self.before
+    }
+
+    /// The board after the tiles slid, but before any tile spawned.
+    pub fn afterstate(&self) -> &Board {
+        &self.afterstate
+    }
+
+    pub fn board_after(&self) -> &Board {
+        &self.after
+    }
+
+    /// The first tile spawned after the move, if any.
+    pub fn spawn(&self) -> Option<Spawn> {
+        self.spawns().into_iter().next()
+    }
+
+    /// Every tile spawned after the move, in board order.
+    pub fn spawns(&self) -> Vec<Spawn> {
+        let width = self.after.width();
+        self.afterstate
+            .empty_cells()
+            .filter_map(|i| {
+                let value = self.after.get_idx(i);
+                (value != 0).then_some(Spawn {
+                    at: (i % width, i / width),
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    /// The path of every tile on the board before the move.
+    pub fn slides(&self) -> Vec<Slide> {
+        let mut slides = vec![];
+        for line in lines(&self.before, self.direction) {
+            // tiles along the line in the direction of travel, with their position
+            let tiles = line
+                .iter()
+                .map(|(x, y)| ((*x, *y), self.before.get(*x, *y)))
+                .filter(|(_, v)| *v != 0)
+                .collect::<Vec<_>>();
+
+            let mut j = 0;
+            let mut i = 0;
+            while i < tiles.len() {
+                let (from, value) = tiles[i];
+                let to = line[j];
+                if i + 1 < tiles.len()
+                    && tiles[i + 1].1 == value
+                    && self.afterstate.get(to.0, to.1) == value + 1
+                {
+                    slides.push(Slide {
+                        from,
+                        to,
+                        value,
+                        merged: true,
+                    });
+                    slides.push(Slide {
+                        from: tiles[i + 1].0,
+                        to,
+                        value,
+                        merged: true,
+                    });
+                    i += 2;
+                } else {
+                    slides.push(Slide {
+                        from,
+                        to,
+                        value,
+                        merged: false,
+                    });
+                    i += 1;
+                }
+                j += 1;
+            }
+        }
+        slides
+    }
+
+    /// Every merge that happened during the move.
+    pub fn merges(&self) -> Vec<Merge> {
+        let mut merges = self
+            .slides()
+            .iter()
+            .filter(|s| s.merged)
+            .map(|s| Merge {
+                at: s.to,
+                value: s.value + 1,
+            })
+            .collect::<Vec<_>>();
+        merges.dedup();
+        merges
+    }
+}
+
+/// Cell positions of every row or column of the board, ordered in the direction tiles travel
+/// for the given move, starting with the cell they slide towards.
+fn lines(board: &Board, direction: Move) -> Vec<Vec<(usize, usize)>> {
+    let (w, h) = (board.width(), board.height());
+    match direction {
+        Move::Left => (0..h).map(|y| (0..w).map(|x| (x, y)).collect()).collect(),
+        Move::Right => (0..h)
+            .map(|y| (0..w).rev().map(|x| (x, y)).collect())
+            .collect(),
+        Move::Up => (0..w).map(|x| (0..h).map(|y| (x, y)).collect()).collect(),
+        Move::Down => (0..w)
+            .map(|x| (0..h).rev().map(|y| (x, y)).collect())
+            .collect(),
+    }
+}
@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+
+use super::{board::check_size, rules::Rules, Game, Move};
+
+/// Everything needed to play a game again: the seed it started from, its size and rules, and the
+/// moves that were played.
+///
+/// Spawns are not stored, since [`Game::new_with_rules`] and [`Game::make_move`] draw them from a
+/// generator seeded with `seed`, so a record is only as long as its move list. Moves are
+/// serialized as a string with one letter per move (`U`, `D`, `L` or `R`).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+#[serde(try_from = "ReplayRepr", into = "ReplayRepr")]
+pub struct Replay {
+    seed: u64,
+    width: u8,
+    height: u8,
+    rules: Rules,
+    moves: Vec<Move>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplayRepr {
+    seed: u64,
+    width: u8,
+    height: u8,
+    rules: Rules,
+    moves: String,
+}
+
+impl From<Replay> for ReplayRepr {
+    fn from(replay: Replay) -> Self {
+        ReplayRepr {
+            seed: replay.seed,
+            width: replay.width,
+            height: replay.height,
+            rules: replay.rules,
+            moves: replay.moves.iter().map(|m| move_letter(*m)).collect(),
+        }
+    }
+}
+
+impl TryFrom<ReplayRepr> for Replay {
+    type Error = String;
+
+    fn try_from(repr: ReplayRepr) -> Result<Self, Self::Error> {
+        check_size(repr.width as usize, repr.height as usize)?;
+        Ok(Replay {
+            seed: repr.seed,
+            width: repr.width,
+            height: repr.height,
+            rules: repr.rules,
+            moves: repr
+                .moves
+                .chars()
+                .map(letter_move)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+fn move_letter(m: Move) -> char {
+    match m {
+        Move::Up => 'U',
+        Move::Down => 'D',
+        Move::Left => 'L',
+        Move::Right => 'R',
+    }
+}
+
+fn letter_move(c: char) -> Result<Move, String> {
+    match c {
+        'U' => Ok(Move::Up),
+        'D' => Ok(Move::Down),
+        'L' => Ok(Move::Left),
+        'R' => Ok(Move::Right),
+        _ => Err(format!(
+            "'{}' is not a move, moves are one of U, D, L or R",
+            c
+        )),
+    }
+}
+
+impl Replay {
+    /// An empty record of a game started with [`Game::new_with_rules`]. Panics if the size is not
+    /// supported.
+    pub fn new(seed: u64, width: usize, height: usize, rules: Rules) -> Self {
+        if let Err(e) = check_size(width, height) {
+            panic!("{}", e);
+        }
+        Replay {
+            seed,
+            width: width as u8,
+            height: height as u8,
+            rules,
+            moves: vec![],
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Adds a move to the record. Only moves that change the board should be recorded, since
+    /// [`Replay::replay`] rejects any other.
+    pub fn push(&mut self, m: Move) {
+        self.moves.push(m);
+    }
+
+    /// The position the game started from, before any move.
+    pub fn start(&self) -> Game {
+        Game::new_with_rules(self.seed, self.width(), self.height(), self.rules)
+    }
+
+    /// Every position of the game, starting with [`Replay::start`] and followed by the position
+    /// after each move. Fails if a move is played after the game ended or does not change the
+    /// board, as such a move could not have been part of a real game.
+    pub fn states(&self) -> Result<Vec<Game>, String> {
+        let mut game = self.start();
+        let mut states = Vec::with_capacity(self.moves.len() + 1);
+        states.push(game);
+        for (i, m) in self.moves.iter().enumerate() {
+            game = play(game, i, *m)?;
+            states.push(game);
+        }
+        Ok(states)
+    }
+
+    /// The final position of the game. See [`Replay::states`] for when this fails.
+    pub fn replay(&self) -> Result<Game, String> {
+        self.moves
+            .iter()
+            .enumerate()
+            .try_fold(self.start(), |game, (i, m)| play(game, i, *m))
+    }
+
+    /// Checks that the record is valid and reaches exactly the claimed score, returning the final
+    /// position if it does.
+    pub fn verify(&self, claimed_score: usize) -> Result<Game, String> {
+        let game = self.replay()?;
+        if *game.get_score() != claimed_score {
+            return Err(format!(
+                "the record scores {}, not the claimed {}",
+                game.get_score(),
+                claimed_score
+            ));
+        }
+        Ok(game)
+    }
+}
+
+/// Plays the move at index `i` of a record.
+fn play(mut game: Game, i: usize, m: Move) -> Result<Game, String> {
+    if game.game_over() {
+        return Err(format!(
+            "move {} ({}) is played after the game ended",
+            i + 1,
+            m
+        ));
+    }
+    if !game.make_move(m).moved() {
+        return Err(format!("move {} ({}) does not change the board", i + 1, m));
+    }
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(seed: u64, num_moves: usize) -> (Replay, Game) {
+        let rules = Rules::default();
+        let mut replay = Replay::new(seed, 4, 4, rules);
+        let mut game = Game::new_with_rules(seed, 4, 4, rules);
+        while replay.moves().len() < num_moves && !game.game_over() {
+            let m = game.available_moves().next().unwrap();
+            game.make_move(m);
+            replay.push(m);
+        }
+        (replay, game)
+    }
+
+    #[test]
+    fn test_replay() {
+        let (replay, game) = record(7, 50);
+        assert_eq!(replay.replay().unwrap(), game);
+
+        let states = replay.states().unwrap();
+        assert_eq!(states.len(), replay.moves().len() + 1);
+        assert_eq!(states[0], replay.start());
+        assert_eq!(states.last().unwrap(), &game);
+
+        assert!(replay.verify(*game.get_score()).is_ok());
+        assert!(replay.verify(*game.get_score() + 4).is_err());
+    }
+
+    #[test]
+    fn test_invalid_move() {
+        let game = "2 . . ./4 . . ./. . . ./. . . .".parse::<Game>().unwrap();
+        assert!(play(game, 0, Move::Left).is_err());
+        assert!(play(game, 0, Move::Right).is_ok());
+    }
+
+    #[test]
+    fn test_serde() {
+        let (replay, _) = record(11, 20);
+        let s = ron::to_string(&replay).unwrap();
+        assert_eq!(ron::from_str::<Replay>(&s).unwrap(), replay);
+        assert!(ron::from_str::<Replay>(&s.replacen("moves:\"", "moves:\"X", 1)).is_err());
+    }
+}
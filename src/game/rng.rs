@@ -0,0 +1,52 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+/// A small, copyable random number generator whose whole state is a single `u64`.
+///
+/// It produces the same stream as `fastrand::Rng`, but can be stored inside a [`Game`](super::Game),
+/// serialized with it, and cloned into independent simulations without touching any global or
+/// thread-local state.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Seeds a new generator from the thread-local `fastrand` generator.
+    pub fn from_entropy() -> Self {
+        Rng::new(fastrand::u64(..))
+    }
+
+    fn with<T>(&mut self, f: impl FnOnce(&fastrand::Rng) -> T) -> T {
+        let rng = fastrand::Rng::with_seed(self.state);
+        let out = f(&rng);
+        self.state = rng.get_seed();
+        out
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        self.with(|rng| rng.u64(..))
+    }
+
+    pub fn usize(&mut self, range: Range<usize>) -> usize {
+        self.with(|rng| rng.usize(range))
+    }
+
+    pub fn f32(&mut self) -> f32 {
+        self.with(|rng| rng.f32())
+    }
+
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        self.with(|rng| rng.shuffle(slice))
+    }
+
+    /// Derives a new, independent generator from this one, advancing this one in the process.
+    pub fn split(&mut self) -> Rng {
+        Rng::new(self.u64())
+    }
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+
+use super::board::MAX_EXPONENT;
+
+/// How many different tiles a rule set may spawn.
+pub const MAX_SPAWN_KINDS: usize = 4;
+
+/// The variable parts of the game: which tiles spawn and how often, and when the game is won.
+/// Merging is not among them: two equal tiles always merge into one, since the board's move
+/// tables are built for that.
+///
+/// Rules are stored inline, without any allocation, so that games stay cheap to clone during
+/// search. They are serialized in a readable form, with tiles written as their face value.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "RulesRepr", into = "RulesRepr")]
+pub struct Rules {
+    // (exponent, weight) of each tile that can spawn
+    spawns: [(u8, u16); MAX_SPAWN_KINDS],
+    num_spawn_kinds: u8,
+    total_weight: u32,
+    spawns_per_move: u8,
+    initial_tiles: u8,
+    win_exponent: u8,
+    continue_after_win: bool,
+}
+
+/// A tile that can spawn, and its relative weight among the other spawnable tiles.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct SpawnWeight {
+    pub tile: u32,
+    pub weight: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RulesRepr {
+    spawns: Vec<SpawnWeight>,
+    spawns_per_move: u8,
+    initial_tiles: u8,
+    win_tile: u32,
+    continue_after_win: bool,
+}
+
+impl From<Rules> for RulesRepr {
+    fn from(rules: Rules) -> Self {
+        RulesRepr {
+            spawns: rules.spawn_weights(),
+            spawns_per_move: rules.spawns_per_move,
+            initial_tiles: rules.initial_tiles,
+            win_tile: rules.win_tile(),
+            continue_after_win: rules.continue_after_win,
+        }
+    }
+}
+
+impl TryFrom<RulesRepr> for Rules {
+    type Error = String;
+
+    fn try_from(repr: RulesRepr) -> Result<Self, Self::Error> {
+        Rules::new(
+            &repr.spawns,
+            repr.spawns_per_move,
+            repr.initial_tiles,
+            repr.win_tile,
+            repr.continue_after_win,
+        )
+    }
+}
+
+/// The exponent of a tile value, if it is a power of two that fits on the board.
+fn tile_exponent(tile: u32) -> Result<u8, String> {
+    if !tile.is_power_of_two() || tile < 2 || tile.trailing_zeros() > MAX_EXPONENT as u32 {
+        return Err(format!(
+            "{} is not a valid tile, tiles are powers of two from 2 to {}",
+            tile,
+            1 << MAX_EXPONENT
+        ));
+    }
+    Ok(tile.trailing_zeros() as u8)
+}
+
+impl Default for Rules {
+    /// The classic rules: a 2 spawns 90% of the time and a 4 otherwise, one tile per move, two
+    /// tiles to start, and play carries on after reaching 2048.
+    fn default() -> Self {
+        Rules::new(
+            &[
+                SpawnWeight { tile: 2, weight: 9 },
+                SpawnWeight { tile: 4, weight: 1 },
+            ],
+            1,
+            2,
+            2048,
+            true,
+        )
+        .unwrap()
+    }
+}
+
+impl Rules {
+    pub fn new(
+        spawns: &[SpawnWeight],
+        spawns_per_move: u8,
+        initial_tiles: u8,
+        win_tile: u32,
+        continue_after_win: bool,
+    ) -> Result<Self, String> {
+        if spawns.is_empty() || spawns.len() > MAX_SPAWN_KINDS {
+            return Err(format!(
+                "between 1 and {} kinds of tile can spawn, found {}",
+                MAX_SPAWN_KINDS,
+                spawns.len()
+            ));
+        }
+        let total_weight = spawns.iter().map(|s| s.weight as u32).sum();
+        if total_weight == 0 {
+            return Err("spawn weights must not all be zero".to_string());
+        }
+        if spawns_per_move == 0 {
+            return Err("at least one tile must spawn per move".to_string());
+        }
+
+        let mut exponents = [(0, 0); MAX_SPAWN_KINDS];
+        for (e, s) in exponents.iter_mut().zip(spawns) {
+            *e = (tile_exponent(s.tile)?, s.weight);
+        }
+
+        Ok(Rules {
+            spawns: exponents,
+            num_spawn_kinds: spawns.len() as u8,
+            total_weight,
+            spawns_per_move,
+            initial_tiles,
+            win_exponent: tile_exponent(win_tile)?,
+            continue_after_win,
+        })
+    }
+
+    /// Exponents of the tiles that can spawn, with the probability of each.
+    pub fn spawn_probabilities(&self) -> impl Iterator<Item = (u8, f32)> + '_ {
+        self.spawns[..self.num_spawn_kinds as usize]
+            .iter()
+            .map(|(e, w)| (*e, *w as f32 / self.total_weight as f32))
+    }
+
+    pub fn spawn_weights(&self) -> Vec<SpawnWeight> {
+        self.spawns[..self.num_spawn_kinds as usize]
+            .iter()
+            .map(|(e, w)| SpawnWeight {
+                tile: 1 << e,
+                weight: *w,
+            })
+            .collect()
+    }
+
+    /// Picks the exponent of a spawned tile, given a uniform sample `p` in `[0, 1)`.
+    pub fn sample_spawn(&self, mut p: f32) -> u8 {
+        let mut last = 0;
+        for (e, sp) in self.spawn_probabilities() {
+            p -= sp;
+            last = e;
+            if p < 0.0 {
+                return e;
+            }
+        }
+        last
+    }
+
+    pub fn spawns_per_move(&self) -> usize {
+        self.spawns_per_move as usize
+    }
+
+    pub fn initial_tiles(&self) -> usize {
+        self.initial_tiles as usize
+    }
+
+    pub fn win_tile(&self) -> u32 {
+        1 << self.win_exponent
+    }
+
+    pub fn win_exponent(&self) -> u8 {
+        self.win_exponent
+    }
+
+    pub fn continue_after_win(&self) -> bool {
+        self.continue_after_win
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules() {
+        let rules = Rules::default();
+        let probs = rules.spawn_probabilities().collect::<Vec<_>>();
+        assert_eq!(probs.len(), 2);
+        assert_eq!(probs[0].0, 1);
+        assert!((probs[0].1 - 0.9).abs() < 1e-6);
+        assert_eq!(rules.sample_spawn(0.5), 1);
+        assert_eq!(rules.sample_spawn(0.95), 2);
+        assert_eq!(rules.win_exponent(), 11);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let spawn = [SpawnWeight { tile: 2, weight: 1 }];
+        assert!(Rules::new(&spawn, 1, 2, 2048, false).is_ok());
+        assert!(Rules::new(&spawn, 0, 2, 2048, false).is_err());
+        assert!(Rules::new(&spawn, 1, 2, 2000, false).is_err());
+        assert!(Rules::new(&spawn, 1, 2, 1 << 16, false).is_err());
+        assert!(Rules::new(&[], 1, 2, 2048, false).is_err());
+        assert!(Rules::new(&[SpawnWeight { tile: 3, weight: 1 }], 1, 2, 2048, false).is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        let rules = Rules::new(
+            &[
+                SpawnWeight { tile: 2, weight: 3 },
+                SpawnWeight { tile: 8, weight: 1 },
+            ],
+            2,
+            3,
+            512,
+            false,
+        )
+        .unwrap();
+        let s = ron::to_string(&rules).unwrap();
+        assert!(s.contains("win_tile:512"));
+        assert_eq!(ron::from_str::<Rules>(&s).unwrap(), rules);
+    }
+}
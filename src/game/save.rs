@@ -0,0 +1,80 @@
+use std::{error::Error, fs, path::Path};
+
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{history::RecordedGame, replay::Replay, Game};
+
+/// Writes a value to a RON file, creating missing parent directories.
+pub(crate) fn write_ron(value: &impl Serialize, path: &Path) -> Result<(), Box<dyn Error>> {
+    let config = PrettyConfig::new().compact_arrays(true);
+    let contents = ron::ser::to_string_pretty(value, config)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Reads a value from a RON file.
+pub(crate) fn read_ron<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(ron::from_str(&contents)?)
+}
+
+/// Writes a value to a binary file, creating missing parent directories. For values too large
+/// to be worth reading as text.
+pub(crate) fn write_bincode(value: &impl Serialize, path: &Path) -> Result<(), Box<dyn Error>> {
+    let contents = bincode::serialize(value)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Reads a value from a binary file written by [`write_bincode`].
+pub(crate) fn read_bincode<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error>> {
+    let contents = fs::read(path)?;
+    Ok(bincode::deserialize(&contents)?)
+}
+
+impl Game {
+    /// Writes the whole game to a RON file: board, score, move count, the state of the spawn
+    /// generator and the rules. Missing parent directories are created.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_ron(self, path.as_ref())
+    }
+
+    /// Reads a game written by [`Game::save`]. It continues exactly where it left off, spawning
+    /// the same tiles it would have spawned had it never been saved.
+    pub fn load(path: impl AsRef<Path>) -> Result<Game, Box<dyn Error>> {
+        read_ron(path.as_ref())
+    }
+}
+
+impl RecordedGame {
+    /// Writes the game and the moves that can be undone or redone to a RON file. Missing parent
+    /// directories are created.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_ron(self, path.as_ref())
+    }
+
+    /// Reads a game written by [`RecordedGame::save`], with its moves still undoable.
+    pub fn load(path: impl AsRef<Path>) -> Result<RecordedGame, Box<dyn Error>> {
+        read_ron(path.as_ref())
+    }
+}
+
+impl Replay {
+    /// Writes the record to a RON file. Missing parent directories are created.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_ron(self, path.as_ref())
+    }
+
+    /// Reads a record written by [`Replay::save`]. The moves are not checked until the record is
+    /// replayed.
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, Box<dyn Error>> {
+        read_ron(path.as_ref())
+    }
+}
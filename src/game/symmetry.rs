@@ -0,0 +1,258 @@
+//! The eight rotations and reflections of a board.
+//!
+//! Sliding tiles commutes with these symmetries: transforming a board and then playing the
+//! transformed move gives the same position as playing the move and then transforming. Search
+//! and learning can therefore treat all eight images of a position as one.
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+use super::{board::Board, Game, Move};
+
+/// A symmetry of the square, applied to boards of any shape. The ones that turn the board a
+/// quarter swap its width and height.
+#[derive(EnumIter, Debug, PartialEq, Eq, Hash, Clone, Copy, Display, Serialize, Deserialize)]
+pub enum Symmetry {
+    Identity,
+    /// A quarter turn clockwise.
+    Rotate90,
+    Rotate180,
+    /// A quarter turn counterclockwise.
+    Rotate270,
+    /// Mirrors left and right.
+    FlipHorizontal,
+    /// Mirrors top and bottom.
+    FlipVertical,
+    /// Mirrors along the diagonal from the top left corner, swapping rows and columns.
+    Transpose,
+    /// Mirrors along the diagonal from the top right corner.
+    AntiTranspose,
+}
+
+impl Symmetry {
+    /// The symmetry that undoes this one.
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            s => s,
+        }
+    }
+
+    /// Whether the symmetry swaps the width and height of the board.
+    pub fn swaps_sides(self) -> bool {
+        matches!(
+            self,
+            Symmetry::Rotate90
+                | Symmetry::Rotate270
+                | Symmetry::Transpose
+                | Symmetry::AntiTranspose
+        )
+    }
+
+    /// Where cell `(x, y)` of a `width` x `height` board ends up once the board is transformed,
+    /// so that `board.transform(s)` holds `board.get(x, y)` at `s.map_cell((x, y), w, h)`.
+    pub fn map_cell(self, (x, y): (usize, usize), width: usize, height: usize) -> (usize, usize) {
+        let (w, h) = (width - 1, height - 1);
+        match self {
+            Symmetry::Identity => (x, y),
+            Symmetry::Rotate90 => (h - y, x),
+            Symmetry::Rotate180 => (w - x, h - y),
+            Symmetry::Rotate270 => (y, w - x),
+            Symmetry::FlipHorizontal => (w - x, y),
+            Symmetry::FlipVertical => (x, h - y),
+            Symmetry::Transpose => (y, x),
+            Symmetry::AntiTranspose => (h - y, w - x),
+        }
+    }
+
+    /// The direction a move points in once the board is transformed, so that
+    /// `board.transform(s).shift(s.map_move(m))` is `board.shift(m)` transformed.
+    pub fn map_move(self, m: Move) -> Move {
+        let (dx, dy) = match m {
+            Move::Up => (0, -1),
+            Move::Down => (0, 1),
+            Move::Left => (-1, 0),
+            Move::Right => (1, 0),
+        };
+        let (dx, dy) = match self {
+            Symmetry::Identity => (dx, dy),
+            Symmetry::Rotate90 => (-dy, dx),
+            Symmetry::Rotate180 => (-dx, -dy),
+            Symmetry::Rotate270 => (dy, -dx),
+            Symmetry::FlipHorizontal => (-dx, dy),
+            Symmetry::FlipVertical => (dx, -dy),
+            Symmetry::Transpose => (dy, dx),
+            Symmetry::AntiTranspose => (-dy, -dx),
+        };
+        match (dx, dy) {
+            (0, -1) => Move::Up,
+            (0, 1) => Move::Down,
+            (-1, 0) => Move::Left,
+            _ => Move::Right,
+        }
+    }
+}
+
+/// Mirrors left and right.
+fn flip_horizontal(board: &Board) -> Board {
+    let (w, h) = (board.width(), board.height());
+    let mut flipped = Board::empty(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            flipped.set(w - 1 - x, y, board.get(x, y));
+        }
+    }
+    flipped
+}
+
+/// Mirrors top and bottom. Rows are contiguous, so this only moves whole rows.
+fn flip_vertical(board: &Board) -> Board {
+    let (w, h) = (board.width(), board.height());
+    let tiles = board.tiles();
+    let rows = tiles.chunks(w).rev().flatten().copied().collect::<Vec<_>>();
+    let mut flipped = Board::empty(w, h);
+    flipped.set_tiles(&rows);
+    flipped
+}
+
+impl Board {
+    /// The board seen through a symmetry.
+    pub fn transform(&self, symmetry: Symmetry) -> Board {
+        match symmetry {
+            Symmetry::Identity => *self,
+            Symmetry::Rotate90 => flip_horizontal(&self.transpose()),
+            Symmetry::Rotate180 => flip_vertical(&flip_horizontal(self)),
+            Symmetry::Rotate270 => flip_vertical(&self.transpose()),
+            Symmetry::FlipHorizontal => flip_horizontal(self),
+            Symmetry::FlipVertical => flip_vertical(self),
+            Symmetry::Transpose => self.transpose(),
+            Symmetry::AntiTranspose => flip_vertical(&flip_horizontal(&self.transpose())),
+        }
+    }
+
+    /// The smallest of the eight images of the board, with the symmetry that produces it. Two
+    /// boards are equivalent exactly when their canonical forms are equal.
+    pub fn canonical(&self) -> (Board, Symmetry) {
+        Symmetry::iter()
+            .map(|s| (self.transform(s), s))
+            .min_by_key(|(b, _)| (b.width(), b.height(), b.packed()))
+            .unwrap()
+    }
+
+    /// A 64-bit key that is the same for every image of the board. Among boards of the same size
+    /// with at most 16 cells the key is exact, since the canonical cells fit in 64 bits; larger
+    /// boards are hashed, and may rarely collide. The size itself isn't part of the key, so a 2x8
+    /// and a 4x4 board holding the same cells share it: callers mixing sizes key on them too.
+    pub fn position_key(&self) -> u64 {
+        let (board, _) = self.canonical();
+        let cells = board.packed();
+        if board.num_cells() <= 16 {
+            return cells as u64;
+        }
+        mix(cells as u64 ^ mix((cells >> 64) as u64 ^ ((board.width() as u64) << 56)))
+    }
+}
+
+/// The finalizer of splitmix64, which spreads every input bit over the whole output.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+impl Game {
+    /// The game with its board seen through a symmetry. Score, move count, rules and the spawn
+    /// generator are kept.
+    pub fn transform(&self, symmetry: Symmetry) -> Game {
+        Game {
+            board: self.board.transform(symmetry),
+            ..*self
+        }
+    }
+
+    /// The game with its board in canonical form (see [`Board::canonical`]), and the symmetry
+    /// that was applied. A move chosen on the canonical game is played on this one as
+    /// `symmetry.inverse().map_move(m)`.
+    pub fn canonical(&self) -> (Game, Symmetry) {
+        let (board, symmetry) = self.board.canonical();
+        (Game { board, ..*self }, symmetry)
+    }
+
+    /// A compact key identifying the position up to symmetry, for caches and learned evaluators.
+    /// Unlike the derived `Hash`, it only depends on the board, not on the score, move count or
+    /// spawn generator. See [`Board::position_key`].
+    pub fn position_key(&self) -> u64 {
+        self.board.position_key()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform() {
+        let board = "2 4 8/. . 16".parse::<Board>().unwrap();
+        let t = |s| board.transform(s).to_string();
+        assert_eq!(t(Symmetry::Rotate90), " . 2\n . 4\n16 8");
+        assert_eq!(t(Symmetry::Rotate180), "16 . .\n 8 4 2");
+        assert_eq!(t(Symmetry::Rotate270), "8 16\n4  .\n2  .");
+        assert_eq!(t(Symmetry::FlipHorizontal), " 8 4 2\n16 . .");
+        assert_eq!(t(Symmetry::FlipVertical), ". . 16\n2 4  8");
+        assert_eq!(t(Symmetry::Transpose), "2  .\n4  .\n8 16");
+        assert_eq!(t(Symmetry::AntiTranspose), "16 8\n . 4\n . 2");
+
+        for s in Symmetry::iter() {
+            assert_eq!(board.transform(s).transform(s.inverse()), board);
+            for (x, y) in [(0, 0), (2, 0), (1, 1), (2, 1)] {
+                let (tx, ty) = s.map_cell((x, y), 3, 2);
+                assert_eq!(board.transform(s).get(tx, ty), board.get(x, y), "{}", s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_moves_commute() {
+        let board = "2 2 . 4/. 4 8 8/2 . . 2/16 . 2 .".parse::<Board>().unwrap();
+        for s in Symmetry::iter() {
+            for m in Move::iter() {
+                let (moved, score) = board.shift(m);
+                let (transformed, transformed_score) = board.transform(s).shift(s.map_move(m));
+                assert_eq!(transformed, moved.transform(s), "{} {}", s, m);
+                assert_eq!(transformed_score, score);
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        let game = "2 . . ./. . . ./. . . ./. . 4 8".parse::<Game>().unwrap();
+        let key = game.position_key();
+        for s in Symmetry::iter() {
+            let image = game.transform(s);
+            assert_eq!(image.position_key(), key);
+            assert_eq!(
+                image.canonical().0.get_board(),
+                game.canonical().0.get_board()
+            );
+        }
+        let (canonical, s) = game.canonical();
+        assert_eq!(
+            canonical.transform(s.inverse()).get_board(),
+            game.get_board()
+        );
+
+        let other = "2 . . ./. . . ./. . . ./. 8 4 .".parse::<Game>().unwrap();
+        assert_ne!(other.position_key(), key);
+
+        let wide = "2 . . . . . . ./. . . . . . 4 8/. . . . . . . ./. . . . . . . 2"
+            .parse::<Board>()
+            .unwrap();
+        assert_eq!(
+            wide.position_key(),
+            wide.transform(Symmetry::Rotate90).position_key()
+        );
+    }
+}
@@ -1,3 +1,5 @@
 pub mod agent;
+pub mod eval;
 pub mod game;
+pub mod storage;
 pub mod tui;
@@ -0,0 +1,38 @@
+use std::{error::Error, path::PathBuf};
+
+use etcetera::app_strategy::{choose_app_strategy, AppStrategy, AppStrategyArgs};
+
+/// The directory the TUI and the CLI keep their files in, following the platform's conventions.
+pub fn data_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let strategy = choose_app_strategy(AppStrategyArgs {
+        top_level_domain: "com".to_string(),
+        author: "chrisvander".to_string(),
+        app_name: "ai-2048".to_string(),
+    })?;
+    Ok(strategy.data_dir())
+}
+
+/// Where the game saved from the TUI lives.
+pub fn save_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join("save.ron"))
+}
+
+/// Where weights tuned for the Expectimax heuristic live.
+pub fn weights_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join("weights.ron"))
+}
+
+/// Where the n-tuple network trained from the TUI lives.
+pub fn ntuple_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join("ntuple.bin"))
+}
+
+/// Where the value function learned from the TUI lives.
+pub fn value_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join("value.ron"))
+}
+
+/// Where the statistics of finished games live.
+pub fn stats_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(data_dir()?.join("stats.ron"))
+}
@@ -7,7 +7,7 @@ use tui::{
     Frame,
 };
 
-use crate::game::Game;
+use crate::game::{outcome::MoveOutcome, Game};
 
 fn get_color_for_value(v: u32) -> Color {
     match v {
@@ -31,7 +31,7 @@ fn get_color_for_value(v: u32) -> Color {
     }
 }
 
-fn get_table_cell(c: &u32) -> Cell<'_> {
+fn get_table_cell(c: &u32, modifier: Modifier) -> Cell<'_> {
     let cstr = if *c == 1 {
         String::from("")
     } else {
@@ -39,7 +39,7 @@ fn get_table_cell(c: &u32) -> Cell<'_> {
     };
 
     let cell_style = Style::default()
-        .add_modifier(Modifier::BOLD)
+        .add_modifier(Modifier::BOLD | modifier)
         .bg(get_color_for_value(*c));
 
     let front_padding = " ".repeat(5 - (cstr.len() / 2));
@@ -48,24 +48,43 @@ fn get_table_cell(c: &u32) -> Cell<'_> {
     Cell::from(Text::from(cell_body)).style(cell_style)
 }
 
-pub fn render_board<B: Backend>(f: &mut Frame<B>, game: &Game, rect: Rect) {
+/// Marks the tiles that changed in the last move: merged tiles are underlined and newly spawned
+/// tiles are italic.
+fn get_cell_modifier(outcome: Option<&MoveOutcome>, x: usize, y: usize) -> Modifier {
+    let Some(outcome) = outcome else {
+        return Modifier::empty();
+    };
+    if outcome.spawns().iter().any(|s| s.at == (x, y)) {
+        Modifier::ITALIC
+    } else if outcome.merges().iter().any(|m| m.at == (x, y)) {
+        Modifier::UNDERLINED
+    } else {
+        Modifier::empty()
+    }
+}
+
+pub fn render_board<B: Backend>(
+    f: &mut Frame<B>,
+    game: &Game,
+    outcome: Option<&MoveOutcome>,
+    rect: Rect,
+) {
     let block = Block::default().title("Game").borders(Borders::ALL);
     let game_state = game.get_table();
     let selected_style = Style::default().add_modifier(Modifier::REVERSED);
-    let rows = game_state.iter().map(|row| {
-        let row = row.iter().map(get_table_cell);
+    let rows = game_state.iter().enumerate().map(|(y, row)| {
+        let row = row
+            .iter()
+            .enumerate()
+            .map(|(x, c)| get_table_cell(c, get_cell_modifier(outcome, x, y)));
         Row::new(row).height(5).bottom_margin(1)
     });
+    let widths = vec![Constraint::Length(11); game.width()];
     let t = Table::new(rows)
         .block(block)
         .highlight_style(selected_style)
         .highlight_symbol(">> ")
-        .widths(&[
-            Constraint::Length(11),
-            Constraint::Length(11),
-            Constraint::Length(11),
-            Constraint::Length(11),
-        ]);
+        .widths(&widths);
 
     f.render_widget(t, rect);
 }
@@ -11,6 +11,16 @@ pub static MENU_ITEMS: &[&str] = &[
     "Solve (Tree Search, Max Score)",
     "Solve (Tree Search, Max Moves)",
     "Solve (Expectimax)",
+    "Resume Saved Game (Keyboard)",
+    "Solve (Expectimax, Tuned Weights)",
+    "Train (Tune Expectimax Weights)",
+    "Solve (N-Tuple Network)",
+    "Train (N-Tuple Network)",
+    "Solve (Monte Carlo Tree Search)",
+    "Solve (Expectimax, Learned Value)",
+    "Train (Learned Value Function)",
+    "Solve (Corner Strategy)",
+    "Statistics",
 ];
 
 pub static MENU: Lazy<List> = Lazy::new(|| {
@@ -32,12 +42,15 @@ pub fn get_menu<'a>(menu: &List<'a>) -> impl StatefulWidget<State = ListState> +
     list
 }
 
-pub fn get_menu_text() -> impl Widget {
+pub fn get_menu_text(status: Option<&str>) -> impl Widget + '_ {
     let block = Block::default().title("Info").borders(Borders::ALL);
-    let text = vec![
+    let mut text = vec![
         Spans::from("Use arrow keys to navigate"),
         Spans::from("Press q to exit"),
     ];
+    if let Some(status) = status {
+        text.append(&mut vec![Spans::from(""), Spans::from(status)]);
+    }
     let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
     paragraph
 }
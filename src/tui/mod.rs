@@ -1,17 +1,24 @@
-use crate::agent::expectimax::Expectimax;
+use crate::agent::corner::CornerAgent;
+use crate::agent::expectimax::{Expectimax, ExpectimaxParams};
+use crate::agent::heuristic::WeightedSum;
+use crate::agent::learned::LinearValue;
+use crate::agent::mcts::Mcts;
+use crate::agent::ntuple::{NTupleAgent, NTupleNetwork};
 use crate::agent::random::{RandomAgent, RandomTree, RandomTreeMetric};
 use crate::agent::user::UserAgent;
 use crate::agent::TuiAgent;
+use crate::game::history::RecordedGame;
 use crate::game::*;
+use crate::storage;
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::sync::RwLock;
 use std::thread::JoinHandle;
-use std::{error::Error, io, sync::Arc, thread, time::Duration};
+use std::{error::Error, io, path::PathBuf, sync::Arc, thread, time::Duration};
 use tui::widgets::Widget;
 use tui::{
     backend::{Backend, CrosstermBackend},
@@ -24,6 +31,8 @@ use tui::{
 
 mod board;
 mod menu;
+mod stats;
+mod train;
 
 static TICK_RATE: Duration = Duration::from_millis(50);
 
@@ -32,9 +41,14 @@ pub enum Screen {
         state: ListState,
         menu: List<'static>,
     },
-    Train(JoinHandle<()>),
-    // join handle for multithreading if needed
-    Game(JoinHandle<()>, Arc<RwLock<Box<dyn TuiAgent + Sync + Send>>>),
+    Train(JoinHandle<()>, Arc<RwLock<train::TrainProgress>>),
+    // join handle for multithreading if needed, and the mode to record the game under once over
+    Game(
+        JoinHandle<()>,
+        Arc<RwLock<Box<dyn TuiAgent + Sync + Send>>>,
+        Option<String>,
+    ),
+    Stats(stats::Stats),
 }
 
 impl Default for Screen {
@@ -52,19 +66,25 @@ impl Default for Screen {
 #[derive(Default)]
 pub struct App {
     screen: Screen,
+    // feedback from the last action that has no screen of its own, like saving
+    status: Option<String>,
+    // where games are saved and resumed from, instead of the data directory
+    save_path: Option<PathBuf>,
 }
 
-fn get_train_text() -> impl Widget {
-    let block = Block::default().title("Training").borders(Borders::ALL);
-    let text = vec![
-        Spans::from("Training in progress..."),
-        Spans::from("Press q to exit"),
-    ];
-    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
-    paragraph
+/// The path given to save games to, or the one in the data directory.
+fn save_path(path: Option<&PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    match path {
+        Some(path) => Ok(path.clone()),
+        None => storage::save_path(),
+    }
 }
 
-fn get_game_text<'a>(game: &Game, mut agent_spans: Vec<Spans<'a>>) -> impl Widget + 'a {
+fn get_game_text<'a>(
+    game: &Game,
+    mut agent_spans: Vec<Spans<'a>>,
+    status: Option<&'a str>,
+) -> impl Widget + 'a {
     let block = Block::default().title("Info").borders(Borders::ALL);
     let game_over_style = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
     let bold_span = |s| Span::styled(s, Style::default().add_modifier(Modifier::BOLD));
@@ -82,10 +102,27 @@ fn get_game_text<'a>(game: &Game, mut agent_spans: Vec<Spans<'a>>) -> impl Widge
         } else {
             Span::from("")
         }),
+        Spans::from(if game.won() {
+            Span::styled(
+                format!("Reached {}!", game.get_rules().win_tile()),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            Span::from("")
+        }),
         Spans::from(""),
     ];
     text.append(&mut agent_spans);
-    text.append(&mut vec![Spans::from(""), Spans::from("Press q to exit")]);
+    text.append(&mut vec![
+        Spans::from(""),
+        Spans::from("Press Ctrl+S to save the game"),
+        Spans::from("Press q to exit"),
+    ]);
+    if let Some(status) = status {
+        text.push(Spans::from(status));
+    }
     let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
     paragraph
 }
@@ -100,18 +137,42 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     match &mut app.screen {
         Screen::Menu { state, menu } => {
             f.render_stateful_widget(menu::get_menu(menu), chunks[0], state);
-            f.render_widget(menu::get_menu_text(), chunks[1]);
+            f.render_widget(menu::get_menu_text(app.status.as_deref()), chunks[1]);
         }
-        Screen::Train(_) => f.render_widget(get_train_text(), chunks[0]),
-        Screen::Game(_, game_sim) => {
+        Screen::Train(_, progress) => {
+            let progress = progress.read().unwrap();
+            let max_lines = chunks[0].height.saturating_sub(6) as usize;
+            f.render_widget(train::get_train_text(&progress, max_lines), chunks[0]);
+        }
+        Screen::Stats(stats) => {
+            f.render_widget(stats::get_stats_table(stats), chunks[0]);
+            f.render_widget(menu::get_menu_text(app.status.as_deref()), chunks[1]);
+        }
+        Screen::Game(_, game_sim, _) => {
             let agent = game_sim.read().unwrap();
             let game = agent.get_game();
-            board::render_board(f, &game, chunks[0]);
-            f.render_widget(get_game_text(&game, agent.messages()), chunks[1]);
+            board::render_board(f, game, agent.last_outcome(), chunks[0]);
+            f.render_widget(
+                get_game_text(game, agent.messages(), app.status.as_deref()),
+                chunks[1],
+            );
         }
     }
 }
 
+/// The screen of a game played by `agent` on its own thread, recorded in the statistics under
+/// `mode` once over.
+fn game_screen(agent: Box<dyn TuiAgent + Sync + Send>, mode: &str) -> Screen {
+    let agent = Arc::new(RwLock::new(agent));
+    let local_agent = agent.clone();
+    let t = thread::spawn(move || {
+        while !agent.read().unwrap().get_game().game_over() {
+            agent.write().unwrap().make_move();
+        }
+    });
+    Screen::Game(t, local_agent, Some(mode.to_string()))
+}
+
 pub enum IntAction {
     Continue,
     Exit,
@@ -119,7 +180,8 @@ pub enum IntAction {
 
 pub enum MenuItem {
     Play(Box<dyn TuiAgent + Sync + Send>),
-    Train,
+    Train(&'static str, fn(Arc<RwLock<train::TrainProgress>>)),
+    Stats,
     Exit,
 }
 
@@ -168,32 +230,113 @@ fn get_interaction(app: &mut App, timeout: Duration) -> Result<IntAction, io::Er
                             true,
                         ))),
                         Some(4) => MenuItem::Play(Box::new(Expectimax::new(game))),
+                        Some(5) => {
+                            match save_path(app.save_path.as_ref()).and_then(RecordedGame::load) {
+                                Ok(game) => MenuItem::Play(Box::new(UserAgent::resume(game))),
+                                Err(e) => {
+                                    app.status = Some(format!("Could not resume: {}", e));
+                                    return Ok(IntAction::Continue);
+                                }
+                            }
+                        }
+                        Some(6) => {
+                            let heuristic = storage::weights_path()
+                                .and_then(WeightedSum::load)
+                                .unwrap_or_default();
+                            let params = ExpectimaxParams {
+                                depth: 3,
+                                heuristic: Arc::new(heuristic),
+                                ..ExpectimaxParams::default()
+                            };
+                            MenuItem::Play(Box::new(Expectimax::new_with(game, params)))
+                        }
+                        Some(7) => {
+                            MenuItem::Train("Tuning Expectimax weights", train::tune_weights)
+                        }
+                        Some(8) => match storage::ntuple_path().and_then(NTupleNetwork::load) {
+                            Ok(network) => {
+                                MenuItem::Play(Box::new(NTupleAgent::new(game, Arc::new(network))))
+                            }
+                            Err(e) => {
+                                app.status = Some(format!(
+                                    "Could not load the network, train one first: {}",
+                                    e
+                                ));
+                                return Ok(IntAction::Continue);
+                            }
+                        },
+                        Some(9) => {
+                            MenuItem::Train("Training the n-tuple network", train::train_ntuple)
+                        }
+                        Some(10) => MenuItem::Play(Box::new(Mcts::new(game))),
+                        Some(11) => match storage::value_path().and_then(LinearValue::load) {
+                            Ok(value) => {
+                                let params = ExpectimaxParams {
+                                    depth: 3,
+                                    heuristic: Arc::new(value),
+                                    ..ExpectimaxParams::default()
+                                };
+                                MenuItem::Play(Box::new(Expectimax::new_with(game, params)))
+                            }
+                            Err(e) => {
+                                app.status = Some(format!(
+                                    "Could not load the value function, train one first: {}",
+                                    e
+                                ));
+                                return Ok(IntAction::Continue);
+                            }
+                        },
+                        Some(12) => {
+                            MenuItem::Train("Learning a value function", train::learn_value)
+                        }
+                        Some(13) => MenuItem::Play(Box::new(CornerAgent::new(game))),
+                        Some(14) => MenuItem::Stats,
                         _ => panic!(),
                     };
 
-                    let MenuItem::Play(agent) = item else {
-                        return match item {
-                            MenuItem::Exit => Ok(IntAction::Exit),
-                            _ => Ok(IntAction::Continue),
-                        };
-                    };
-
-                    let agent = Arc::new(RwLock::new(agent));
-                    let local_agent = agent.clone();
-                    let t = thread::spawn(move || {
-                        while !agent.read().unwrap().get_game().game_over() {
-                            agent.write().unwrap().make_move();
+                    let agent = match item {
+                        MenuItem::Play(agent) => agent,
+                        MenuItem::Train(title, trainer) => {
+                            let progress = Arc::new(RwLock::new(train::TrainProgress::new(title)));
+                            let local_progress = progress.clone();
+                            let t = thread::spawn(move || trainer(progress));
+                            app.status = None;
+                            app.screen = Screen::Train(t, local_progress);
+                            return Ok(IntAction::Continue);
                         }
-                    });
+                        MenuItem::Stats => {
+                            match storage::stats_path().and_then(stats::Stats::load) {
+                                Ok(stats) => {
+                                    app.status = None;
+                                    app.screen = Screen::Stats(stats);
+                                }
+                                Err(e) => {
+                                    app.status =
+                                        Some(format!("Could not load the statistics: {}", e))
+                                }
+                            }
+                            return Ok(IntAction::Continue);
+                        }
+                        MenuItem::Exit => return Ok(IntAction::Exit),
+                    };
+                    // a resumed game is still played by keyboard
+                    let mode = match state.selected() {
+                        Some(5) => menu::MENU_ITEMS[0],
+                        Some(i) => menu::MENU_ITEMS[i],
+                        None => unreachable!(),
+                    };
 
-                    app.screen = Screen::Game(t, local_agent);
+                    app.status = None;
+                    app.screen = game_screen(agent, mode);
                 }
                 _ => {}
             };
         }
-        Screen::Train(t) => {
+        Screen::Train(t, progress) => {
             if t.is_finished() {
-                return Ok(IntAction::Exit);
+                app.status = progress.read().unwrap().last_line().map(String::from);
+                app.screen = Screen::default();
+                return Ok(IntAction::Continue);
             }
 
             if !event::poll(timeout)? {
@@ -203,14 +346,44 @@ fn get_interaction(app: &mut App, timeout: Duration) -> Result<IntAction, io::Er
             let Event::Key(key_event) = event else {
                 return Ok(IntAction::Continue);
             };
+            // the screen stays up until the training thread has stopped, so that it never
+            // carries on, or saves, behind the menu
+            if let KeyCode::Char('q') = key_event.code {
+                progress.write().unwrap().stop();
+            };
+        }
+        Screen::Stats(stats) => {
+            if !event::poll(timeout)? {
+                return Ok(IntAction::Continue);
+            }
+            let Event::Key(key_event) = event::read()? else {
+                return Ok(IntAction::Continue);
+            };
             match key_event.code {
-                KeyCode::Char('q') => {
-                    return Ok(IntAction::Exit);
+                KeyCode::Char('q') => return Ok(IntAction::Exit),
+                KeyCode::Char('r') => {
+                    stats.reset();
+                    app.status = Some(
+                        match storage::stats_path().and_then(|path| stats.save(path)) {
+                            Ok(()) => "Statistics reset".to_string(),
+                            Err(e) => format!("Could not reset the statistics: {}", e),
+                        },
+                    );
                 }
                 _ => {}
-            };
+            }
         }
-        Screen::Game(_, agent) => {
+        Screen::Game(_, agent, mode) => {
+            if let Some(name) = mode {
+                let agent = agent.read().unwrap();
+                if agent.get_game().game_over() {
+                    if let Err(e) = record_game(name, agent.get_game()) {
+                        app.status = Some(format!("Could not save the statistics: {}", e));
+                    }
+                    *mode = None;
+                }
+            }
+
             if !event::poll(timeout)? {
                 return Ok(IntAction::Continue);
             }
@@ -219,13 +392,30 @@ fn get_interaction(app: &mut App, timeout: Duration) -> Result<IntAction, io::Er
                 return Ok(IntAction::Continue);
             };
 
-            match key_event.code {
-                KeyCode::Char('q') => {
-                    return Ok(IntAction::Exit);
-                }
-                _ => {}
+            if let KeyCode::Char('q') = key_event.code {
+                return Ok(IntAction::Exit);
             };
 
+            if key_event.code == KeyCode::Char('s')
+                && key_event.modifiers.contains(KeyModifiers::CONTROL)
+            {
+                let game = {
+                    let agent = agent.read().unwrap();
+                    agent
+                        .recorded_game()
+                        .cloned()
+                        .unwrap_or_else(|| RecordedGame::new(*agent.get_game()))
+                };
+                app.status = Some(match save_path(app.save_path.as_ref()) {
+                    Ok(path) => match game.save(&path) {
+                        Ok(()) => format!("Saved to {}", path.display()),
+                        Err(e) => format!("Could not save: {}", e),
+                    },
+                    Err(e) => format!("Could not save: {}", e),
+                });
+                return Ok(IntAction::Continue);
+            }
+
             return Ok(agent.write().unwrap().get_input(&event));
         }
     };
@@ -233,8 +423,15 @@ fn get_interaction(app: &mut App, timeout: Duration) -> Result<IntAction, io::Er
     Ok(IntAction::Continue)
 }
 
-fn tui_interaction_loop<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
-    let mut app = App::default();
+/// Adds a finished game to the saved statistics.
+fn record_game(mode: &str, game: &Game) -> Result<(), Box<dyn Error>> {
+    let path = storage::stats_path()?;
+    let mut stats = stats::Stats::load(&path)?;
+    stats.record(mode, game);
+    stats.save(&path)
+}
+
+fn tui_interaction_loop<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
     let mut last_tick = std::time::Instant::now();
     loop {
         terminal.draw(|f| ui(f, &mut app))?;
@@ -247,7 +444,8 @@ fn tui_interaction_loop<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()
             IntAction::Continue => {}
             IntAction::Exit => match app.screen {
                 Screen::Menu { state: _, menu: _ } => break,
-                Screen::Game(_, _) | Screen::Train(_) => {
+                Screen::Game(_, _, _) | Screen::Train(_, _) | Screen::Stats(_) => {
+                    app.status = None;
                     app.screen = Screen::default();
                     continue;
                 }
@@ -262,7 +460,30 @@ fn tui_interaction_loop<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()
     Ok(())
 }
 
-pub fn start() -> Result<(), Box<dyn Error>> {
+/// Starts the TUI at the menu. Games are saved to and resumed from `save_path`, or
+/// [`storage::save_path`] if not given.
+pub fn start(save_path: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    run(App {
+        save_path,
+        ..App::default()
+    })
+}
+
+/// Starts the TUI straight into a game played by `agent`, recorded in the statistics under
+/// `mode`. Leaving the game goes back to the menu. Games are saved as with [`start`].
+pub fn start_game(
+    agent: Box<dyn TuiAgent + Sync + Send>,
+    mode: &str,
+    save_path: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    run(App {
+        screen: game_screen(agent, mode),
+        status: None,
+        save_path,
+    })
+}
+
+fn run(app: App) -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -270,7 +491,7 @@ pub fn start() -> Result<(), Box<dyn Error>> {
     let mut terminal = Terminal::new(backend).unwrap();
     terminal.clear()?;
 
-    tui_interaction_loop(&mut terminal)?;
+    tui_interaction_loop(&mut terminal, app)?;
 
     disable_raw_mode()?;
     execute!(
@@ -0,0 +1,117 @@
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tui::{
+    layout::Constraint,
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Cell, Row, Table, Widget},
+};
+
+use crate::game::{
+    save::{read_ron, write_ron},
+    Game,
+};
+
+/// Finished games of one menu entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModeStats {
+    pub games: usize,
+    pub high_score: usize,
+    pub best_tile: u32,
+    pub total_score: u64,
+}
+
+impl ModeStats {
+    pub fn average_score(&self) -> f64 {
+        self.total_score as f64 / self.games.max(1) as f64
+    }
+}
+
+/// Statistics of the games finished in the TUI, by the menu entry they were started from. Games
+/// left before they are over don't count.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    modes: BTreeMap<String, ModeStats>,
+}
+
+impl Stats {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_ron(self, path.as_ref())
+    }
+
+    /// Reads the statistics, or starts afresh if none were saved yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Stats, Box<dyn Error>> {
+        if !path.as_ref().exists() {
+            return Ok(Stats::default());
+        }
+        read_ron(path.as_ref())
+    }
+
+    pub fn record(&mut self, mode: &str, game: &Game) {
+        let stats = self.modes.entry(mode.to_string()).or_default();
+        let score = *game.get_score();
+        stats.games += 1;
+        stats.high_score = stats.high_score.max(score);
+        stats.best_tile = stats.best_tile.max(game.max_tile());
+        stats.total_score += score as u64;
+    }
+
+    pub fn modes(&self) -> impl Iterator<Item = (&str, &ModeStats)> {
+        self.modes.iter().map(|(m, s)| (m.as_str(), s))
+    }
+
+    pub fn reset(&mut self) {
+        self.modes.clear();
+    }
+}
+
+pub fn get_stats_table(stats: &Stats) -> impl Widget + '_ {
+    let block = Block::default()
+        .title("Statistics (press r to reset, q to go back)")
+        .borders(Borders::ALL);
+    let header = Row::new(["Mode", "Games", "High Score", "Best Tile", "Average Score"])
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .bottom_margin(1);
+    let rows = stats.modes().map(|(mode, s)| {
+        Row::new(vec![
+            Cell::from(mode),
+            Cell::from(s.games.to_string()),
+            Cell::from(s.high_score.to_string()),
+            Cell::from(s.best_tile.to_string()),
+            Cell::from(format!("{:.0}", s.average_score())),
+        ])
+    });
+    Table::new(rows).header(header).block(block).widths(&[
+        Constraint::Percentage(40),
+        Constraint::Percentage(12),
+        Constraint::Percentage(16),
+        Constraint::Percentage(14),
+        Constraint::Percentage(18),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut stats = Stats::default();
+        let mut game = "2 2 . ./. . . ./. . . ./. . . .".parse::<Game>().unwrap();
+        stats.record("Play", &game);
+        game.make_move(crate::game::Move::Left);
+        stats.record("Play", &game);
+        stats.record("Solve", &game);
+
+        let (_, play) = stats.modes().find(|(m, _)| *m == "Play").unwrap();
+        assert_eq!(play.games, 2);
+        assert_eq!(play.best_tile, game.max_tile());
+        assert_eq!(play.high_score, 4);
+        assert_eq!(play.average_score(), 2.0);
+
+        let s = ron::to_string(&stats).unwrap();
+        assert_eq!(ron::from_str::<Stats>(&s).unwrap(), stats);
+        stats.reset();
+        assert_eq!(stats.modes().count(), 0);
+    }
+}
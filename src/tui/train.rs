@@ -0,0 +1,206 @@
+use std::sync::{Arc, RwLock};
+
+use tui::{
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
+};
+
+use linfa::{traits::Fit, Dataset};
+use ndarray::{Array1, Array2, Axis};
+
+use crate::agent::{
+    expectimax::{Expectimax, ExpectimaxParams},
+    heuristic::WeightedSum,
+    learned::{generate_dataset, RidgeRegression, NUM_FEATURES},
+    ntuple::{self, NTupleNetwork, TdParams},
+    tuning::{tune, TuningParams},
+};
+
+use crate::storage;
+
+/// What a training thread reports to the Train screen, and how the screen asks it to stop.
+#[derive(Default)]
+pub struct TrainProgress {
+    title: String,
+    lines: Vec<String>,
+    stop: bool,
+}
+
+impl TrainProgress {
+    pub fn new(title: &str) -> Self {
+        TrainProgress {
+            title: title.to_string(),
+            ..TrainProgress::default()
+        }
+    }
+
+    /// Asks the training thread to stop once it is done with its current step. It stops without
+    /// saving what it learned since it last saved.
+    pub fn stop(&mut self) {
+        if !self.stop {
+            self.stop = true;
+            self.lines
+                .push("Stopping after the current step...".to_string());
+        }
+    }
+
+    /// The line to end on, depending on whether training was stopped.
+    fn finish(&mut self, done: &str) {
+        let line = if self.stop { "Stopped training" } else { done };
+        self.lines.push(line.to_string());
+    }
+
+    pub fn last_line(&self) -> Option<&str> {
+        self.lines.last().map(String::as_str)
+    }
+}
+
+pub fn get_train_text(progress: &TrainProgress, max_lines: usize) -> impl Widget + '_ {
+    let block = Block::default().title("Training").borders(Borders::ALL);
+    let mut text = vec![
+        Spans::from(Span::from(progress.title.as_str())),
+        Spans::from(""),
+    ];
+    let skip = progress.lines.len().saturating_sub(max_lines);
+    text.extend(
+        progress.lines[skip..]
+            .iter()
+            .map(|l| Spans::from(l.as_str())),
+    );
+    text.append(&mut vec![
+        Spans::from(""),
+        Spans::from("Press q to stop training"),
+    ]);
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+    paragraph
+}
+
+/// Tunes the weights of the Expectimax heuristic, saving the best weights seen so far whenever a
+/// generation improves on them.
+pub fn tune_weights(progress: Arc<RwLock<TrainProgress>>) {
+    let params = TuningParams::default();
+    let path = storage::weights_path();
+    tune(&params, |generation| {
+        if progress.read().unwrap().stop {
+            return false;
+        }
+        let saved = generation.improved.then(|| match &path {
+            Ok(path) => generation
+                .best
+                .weights
+                .save(path)
+                .map(|_| path.display().to_string()),
+            Err(e) => Err(e.to_string().into()),
+        });
+        let mut progress = progress.write().unwrap();
+        progress.lines.push(format!(
+            "Generation {} of {}: best {:.0} ({:.0} held out), mean {:.0}",
+            generation.index + 1,
+            params.generations,
+            generation.best.fitness,
+            generation.holdout_fitness,
+            generation.mean_fitness
+        ));
+        progress.lines.push(match saved {
+            Some(Ok(path)) => format!("Saved the best weights to {}", path),
+            Some(Err(e)) => format!("Could not save the weights: {}", e),
+            None => "Kept the best weights of an earlier generation".to_string(),
+        });
+        !progress.stop
+    });
+    progress.write().unwrap().finish("Done tuning");
+}
+
+/// Trains the n-tuple network by self-play, picking up from the saved network if there is one,
+/// and saving it after every report.
+pub fn train_ntuple(progress: Arc<RwLock<TrainProgress>>) {
+    let path = storage::ntuple_path();
+    let mut network = path
+        .as_ref()
+        .ok()
+        .and_then(|path| NTupleNetwork::load(path).ok())
+        .unwrap_or_else(NTupleNetwork::new_4x4);
+    let params = TdParams {
+        report_every: 500,
+        ..TdParams::default()
+    };
+    ntuple::train(&mut network, &params, |report, network| {
+        if progress.read().unwrap().stop {
+            return false;
+        }
+        let saved = match &path {
+            Ok(path) => network.save(path).map(|_| path.display().to_string()),
+            Err(e) => Err(e.to_string().into()),
+        };
+        let mut progress = progress.write().unwrap();
+        progress.lines.push(format!(
+            "{} games: mean score {:.0}, best {}, {:.1}% won",
+            report.games,
+            report.mean_score,
+            report.max_score,
+            100.0 * report.win_rate
+        ));
+        if let Err(e) = saved {
+            progress
+                .lines
+                .push(format!("Could not save the network: {}", e));
+        }
+        !progress.stop
+    });
+    progress.write().unwrap().finish("Done training");
+}
+
+/// Learns a value function from games of depth 1 Expectimax over the tuned weights, then fits it
+/// and saves it. Games are played in rounds so the screen can show progress, and if it is
+/// stopped between rounds nothing is fitted or saved.
+pub fn learn_value(progress: Arc<RwLock<TrainProgress>>) {
+    const ROUNDS: usize = 10;
+    const GAMES_PER_ROUND: usize = 20;
+    let weights = Arc::new(
+        storage::weights_path()
+            .and_then(WeightedSum::load)
+            .unwrap_or_default(),
+    );
+    let mut records = Array2::zeros((0, NUM_FEATURES));
+    let mut targets = Array1::zeros(0);
+    for round in 0..ROUNDS {
+        let games = generate_dataset(GAMES_PER_ROUND, fastrand::u64(..), |game, seed| {
+            let params = ExpectimaxParams {
+                seed,
+                depth: 1,
+                heuristic: weights.clone(),
+                table_size: 0,
+                ..ExpectimaxParams::default()
+            };
+            Expectimax::new_with(game, params)
+        });
+        records.append(Axis(0), games.records().view()).unwrap();
+        targets.append(Axis(0), games.targets().view()).unwrap();
+        let mut progress = progress.write().unwrap();
+        progress.lines.push(format!(
+            "Played {} of {} games, {} positions",
+            (round + 1) * GAMES_PER_ROUND,
+            ROUNDS * GAMES_PER_ROUND,
+            targets.len()
+        ));
+        if progress.stop {
+            progress
+                .lines
+                .push("Stopped before fitting, the saved value function is unchanged".to_string());
+            return;
+        }
+    }
+
+    let result = RidgeRegression::default()
+        .fit(&Dataset::new(records, targets))
+        .map_err(|e| e.to_string())
+        .and_then(|value| {
+            let path = storage::value_path().map_err(|e| e.to_string())?;
+            value.save(&path).map_err(|e| e.to_string())?;
+            Ok(path)
+        });
+    progress.write().unwrap().lines.push(match result {
+        Ok(path) => format!("Saved the value function to {}", path.display()),
+        Err(e) => format!("Could not learn the value function: {}", e),
+    });
+}